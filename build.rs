@@ -1,20 +1,21 @@
+use bindgen::callbacks;
 use bindgen::RustTarget;
-use bindgen::{callbacks, Bindings};
 use camino::Utf8Path as Path;
 use camino::Utf8PathBuf as PathBuf;
 use once_cell::sync::Lazy;
 use std::{collections::HashSet, env, fs};
 
-/// All the libs that FFmpeg has
+/// All the libs that FFmpeg has, in static linking order(a library is placed
+/// before the libraries it depends on).
 static LIBS: Lazy<[&str; 7]> = Lazy::new(|| {
     [
-        "avcodec",
         "avdevice",
         "avfilter",
         "avformat",
-        "avutil",
+        "avcodec",
         "swresample",
         "swscale",
+        "avutil",
     ]
 });
 
@@ -100,6 +101,7 @@ static HEADERS: Lazy<Vec<PathBuf>> = Lazy::new(|| {
         "libavutil/hdr_dynamic_vivid_metadata.h",
         "libavutil/hmac.h",
         "libavutil/hwcontext.h",
+        // Some of the API specific headers are opt-in, check `HW_APIS`.
         // "libavutil/hwcontext_cuda.h",
         // "libavutil/hwcontext_d3d11va.h",
         // "libavutil/hwcontext_drm.h",
@@ -164,6 +166,229 @@ static HEADERS: Lazy<Vec<PathBuf>> = Lazy::new(|| {
     .collect()
 });
 
+/// Hardware acceleration API whose headers are only added to bindgen when
+/// its feature is enabled, since they include the headers of the system API
+/// (e.g. `va/va.h`), which are usually not installed.
+struct HwApi {
+    feature: &'static str,
+    enabled: bool,
+    headers: &'static [&'static str],
+    /// System headers included by the FFmpeg headers, any of them is enough.
+    system_headers: &'static [&'static str],
+    /// pkg-config package providing the system headers.
+    #[cfg_attr(target_os = "windows", allow(dead_code))]
+    pkg_config_name: Option<&'static str>,
+    /// Environment variables of SDK roots, whose `include` directory
+    /// contains the system headers.
+    sdk_env_vars: &'static [&'static str],
+}
+
+static HW_APIS: Lazy<[HwApi; 6]> = Lazy::new(|| {
+    [
+        HwApi {
+            feature: "hw_cuda",
+            enabled: cfg!(feature = "hw_cuda"),
+            headers: &["libavutil/hwcontext_cuda.h"],
+            system_headers: &["cuda.h"],
+            pkg_config_name: None,
+            sdk_env_vars: &["CUDA_PATH", "CUDA_HOME"],
+        },
+        HwApi {
+            feature: "hw_drm",
+            enabled: cfg!(feature = "hw_drm"),
+            headers: &["libavutil/hwcontext_drm.h"],
+            system_headers: &[],
+            pkg_config_name: None,
+            sdk_env_vars: &[],
+        },
+        HwApi {
+            feature: "hw_opencl",
+            enabled: cfg!(feature = "hw_opencl"),
+            headers: &["libavutil/hwcontext_opencl.h"],
+            system_headers: &["CL/cl.h", "OpenCL/cl.h"],
+            pkg_config_name: Some("OpenCL"),
+            sdk_env_vars: &["OCL_ROOT", "CUDA_PATH"],
+        },
+        HwApi {
+            feature: "hw_vaapi",
+            enabled: cfg!(feature = "hw_vaapi"),
+            headers: &["libavutil/hwcontext_vaapi.h"],
+            system_headers: &["va/va.h"],
+            pkg_config_name: Some("libva"),
+            sdk_env_vars: &[],
+        },
+        HwApi {
+            feature: "hw_vdpau",
+            enabled: cfg!(feature = "hw_vdpau"),
+            headers: &["libavcodec/vdpau.h", "libavutil/hwcontext_vdpau.h"],
+            system_headers: &["vdpau/vdpau.h"],
+            pkg_config_name: Some("vdpau"),
+            sdk_env_vars: &[],
+        },
+        HwApi {
+            feature: "hw_vulkan",
+            enabled: cfg!(feature = "hw_vulkan"),
+            headers: &["libavutil/hwcontext_vulkan.h"],
+            system_headers: &["vulkan/vulkan.h"],
+            pkg_config_name: Some("vulkan"),
+            sdk_env_vars: &["VULKAN_SDK"],
+        },
+    ]
+});
+
+/// FFmpeg enums generated as `#[non_exhaustive]` newtypes with the
+/// `typed_enums` feature, their variants are still free constants.
+static TYPED_ENUMS: Lazy<[&str; 4]> = Lazy::new(|| {
+    [
+        "AVCodecID",
+        "AVMediaType",
+        "AVPixelFormat",
+        "AVSampleFormat",
+    ]
+});
+
+/// Items used by the hand-written code of this crate (`src/avutil`, the safe
+/// wrappers and `typed_enums`), which are always generated when
+/// `FFMPEG_BINDGEN_ALLOWLIST` is set. CI builds the library with a minimal
+/// allowlist on every FFmpeg version to check the list is complete.
+static REQUIRED_ITEMS: Lazy<Vec<&str>> = Lazy::new(|| {
+    vec![
+        // src/avutil
+        "AVRational",
+        "av_reduce",
+        "AV_NOPTS_VALUE",
+        "AV_TIME_BASE",
+        "av_strerror",
+        "AV_ERROR_MAX_STRING_SIZE",
+        "E[A-Z0-9]+",
+        "AVPixelFormat",
+        "AV_PIX_FMT_.*",
+        "AVChannel.*",
+        "AV_CHAN_.*",
+        "AV_CHANNEL_ORDER_.*",
+        // src/audio_fifo.rs, src/frame.rs
+        "AVAudioFifo",
+        "av_audio_fifo_.*",
+        "av_frame_clone",
+        "av_frame_unref",
+        // src/bitstream_filter.rs, src/packet.rs
+        "AVBSFContext",
+        "av_bsf_.*",
+        "avcodec_parameters_alloc",
+        "avcodec_parameters_copy",
+        "avcodec_parameters_free",
+        // Also src/typed_enums.rs.
+        "AVMEDIA_TYPE_.*",
+        "AVPacket",
+        "av_packet_.*",
+        "av_new_packet",
+        // src/compat.rs
+        "AVFrame",
+        "AVCodecContext",
+        "AVCodecParameters",
+        "AV_FRAME_FLAG_.*",
+        "av_channel_layout_.*",
+        "av_get_default_channel_layout",
+        "avcodec_get_supported_config",
+        "AV_CODEC_CONFIG_.*",
+        // src/error.rs, src/hwcontext.rs
+        "AVBufferRef",
+        "av_buffer_ref",
+        "av_buffer_unref",
+        "AVCodec",
+        "av_codec_iterate",
+        "av_codec_is_decoder",
+        "avcodec_get_hw_config",
+        "AV_CODEC_HW_CONFIG_METHOD_.*",
+        "AVHWDevice.*",
+        "AV_HWDEVICE_TYPE_.*",
+        "av_hwdevice_.*",
+        // src/fifo.rs
+        "AVFifo",
+        "AVFifoCB",
+        "AV_FIFO_FLAG_.*",
+        "av_fifo_.*",
+        // src/image.rs
+        "av_image_.*",
+        // src/parser.rs
+        "AVCodecParserContext",
+        "av_parser_.*",
+        "avcodec_alloc_context3",
+        "avcodec_free_context",
+        "AV_INPUT_BUFFER_PADDING_SIZE",
+        "AV_PKT_FLAG_.*",
+        "AVPictureType",
+        "AV_PICTURE_TYPE_.*",
+        // Also src/typed_enums.rs.
+        "AVCodecID",
+        "AV_CODEC_ID_.*",
+        // src/pixel_format.rs
+        "AVPixFmtDescriptor",
+        "AV_PIX_FMT_FLAG_.*",
+        "av_get_pix_fmt",
+        "av_get_bits_per_pixel",
+        "av_get_padded_bits_per_pixel",
+        "av_pix_fmt_count_planes",
+        "av_pix_fmt_swap_endianness",
+        "av_find_best_pix_fmt_of_2",
+        // src/probe.rs
+        "AVProbeData",
+        "av_probe_input_format3",
+        "AVPROBE_.*",
+        // src/registry.rs
+        "AVCodecDescriptor",
+        "AVProfile",
+        "AVInputFormat",
+        "AVOutputFormat",
+        "AVFilter",
+        "AVBitStreamFilter",
+        "av_codec_is_encoder",
+        "avcodec_profile_name",
+        // Also src/typed_enums.rs.
+        "avcodec_descriptor_get",
+        "AV_CODEC_PROP_.*",
+        "AV_CODEC_CAP_.*",
+        "av_demuxer_iterate",
+        "av_muxer_iterate",
+        "av_filter_iterate",
+        "avio_enum_protocols",
+        "avdevice_register_all",
+        "av_(input|output)_(audio|video)_device_next",
+        // src/sample_format.rs
+        "av_get_sample_fmt",
+        "av_get_bytes_per_sample",
+        "av_sample_fmt_is_planar",
+        "av_get_packed_sample_fmt",
+        "av_get_planar_sample_fmt",
+        "av_samples_.*",
+        "av_frame_alloc",
+        "av_frame_free",
+        "av_frame_get_buffer",
+        "av_freep",
+        // src/seek.rs
+        "AVFormatContext",
+        "AVStream",
+        "AVSEEK_FLAG_.*",
+        "av_rescale_q",
+        "avformat_seek_file",
+        "av_seek_frame",
+        "av_read_frame",
+        "avcodec_flush_buffers",
+        "avcodec_send_packet",
+        "avcodec_receive_frame",
+        // src/typed_enums.rs, along with the items shared with the modules
+        // above.
+        "AVMediaType",
+        "AVSampleFormat",
+        "AV_SAMPLE_FMT_.*",
+        "av_get_media_type_string",
+        "av_get_pix_fmt_name",
+        "av_get_sample_fmt_name",
+        "av_pix_fmt_desc_get",
+        "avcodec_get_name",
+    ]
+});
+
 /// Filter out all symbols in the HashSet, and for others things it will act
 /// exactly the same as `CargoCallback`.
 #[derive(Debug)]
@@ -185,6 +410,17 @@ impl callbacks::ParseCallbacks for FilterCargoCallbacks {
             callbacks::MacroParsingBehavior::Default
         }
     }
+
+    fn add_attributes(&self, info: &callbacks::AttributeInfo<'_>) -> Vec<String> {
+        if cfg!(feature = "typed_enums")
+            && info.kind == callbacks::TypeKind::Enum
+            && TYPED_ENUMS.contains(&info.name)
+        {
+            vec!["#[non_exhaustive]".to_string()]
+        } else {
+            vec![]
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -223,7 +459,83 @@ fn use_prebuilt_binding(from: &Path, to: &Path) {
     fs::copy(from, to).expect("Prebuilt binding file failed to be copied.");
 }
 
-fn generate_bindings(ffmpeg_include_dir: &Path, headers: &[PathBuf]) -> Bindings {
+/// Extra clang arguments for cross compilation. The target is passed to
+/// clang by bindgen itself, `BINDGEN_EXTRA_CLANG_ARGS` too.
+fn cross_clang_args(env_vars: &EnvVars) -> Vec<String> {
+    env_vars
+        .ffmpeg_sysroot
+        .iter()
+        .map(|ffmpeg_sysroot| format!("--sysroot={ffmpeg_sysroot}"))
+        .collect()
+}
+
+/// Returns the headers and the system include directories of the enabled
+/// hardware acceleration APIs. APIs whose system headers are not found are
+/// skipped with a warning.
+fn hw_api_headers(env_vars: &EnvVars, ffmpeg_include_dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut headers = Vec::new();
+    let mut include_dirs = Vec::new();
+    for api in HW_APIS.iter().filter(|api| api.enabled) {
+        let mut api_include_dirs = Vec::new();
+        for sdk_env_var in api.sdk_env_vars {
+            println!("cargo:rerun-if-env-changed={sdk_env_var}");
+            if let Ok(sdk_root) = env::var(sdk_env_var) {
+                api_include_dirs.push(remove_verbatim(sdk_root).join("include"));
+            }
+        }
+        #[cfg(not(target_os = "windows"))]
+        if let Some(pkg_config_name) = api.pkg_config_name {
+            if let Ok(library) = pkg_config::Config::new()
+                .cargo_metadata(false)
+                .env_metadata(false)
+                .probe(pkg_config_name)
+            {
+                api_include_dirs.extend(
+                    library
+                        .include_paths
+                        .into_iter()
+                        .filter_map(|x| PathBuf::from_path_buf(x).ok()),
+                );
+            }
+        }
+
+        let mut search_dirs = api_include_dirs.clone();
+        search_dirs.push(ffmpeg_include_dir.to_path_buf());
+        for env_var in ["CPATH", "C_INCLUDE_PATH"] {
+            if let Some(paths) = env::var_os(env_var) {
+                search_dirs.extend(
+                    env::split_paths(&paths).filter_map(|x| PathBuf::from_path_buf(x).ok()),
+                );
+            }
+        }
+        let sysroot = env_vars
+            .ffmpeg_sysroot
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("/"));
+        search_dirs.push(sysroot.join("usr/include"));
+        search_dirs.push(sysroot.join("usr/local/include"));
+
+        let found = api.system_headers.is_empty()
+            || api.system_headers.iter().any(|system_header| {
+                search_dirs
+                    .iter()
+                    .any(|dir| dir.join(system_header).exists())
+            });
+        if !found {
+            println!(
+                "cargo:warning=rusty_ffmpeg: feature `{}` is enabled, but `{}` is not found, its bindings are skipped.",
+                api.feature,
+                api.system_headers.join("` or `"),
+            );
+            continue;
+        }
+        headers.extend(api.headers.iter().map(PathBuf::from));
+        include_dirs.extend(api_include_dirs);
+    }
+    (headers, include_dirs)
+}
+
+fn generate_bindings(env_vars: &EnvVars, ffmpeg_include_dir: &Path, headers: &[PathBuf]) -> String {
     if !Path::new(ffmpeg_include_dir).exists() {
         panic!(
             "FFmpeg include dir: `{:?}` doesn't exits",
@@ -243,9 +555,24 @@ fn generate_bindings(ffmpeg_include_dir: &Path, headers: &[PathBuf]) -> Bindings
         .collect(),
     );
 
+    let (hw_headers, hw_include_dirs) = hw_api_headers(env_vars, ffmpeg_include_dir);
+
+    // Relative to the include dir, or absolute.
+    let extra_headers: Vec<PathBuf> = env_vars
+        .ffmpeg_extra_headers
+        .as_ref()
+        .map(|paths| {
+            env::split_paths(paths)
+                .filter_map(|x| PathBuf::from_path_buf(x).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
     // Bindgen on all avaiable headers
-    headers
+    let headers: Vec<PathBuf> = headers
         .iter()
+        .chain(&hw_headers)
+        .chain(&extra_headers)
         .map(|header| ffmpeg_include_dir.join(header))
         .filter(|path| {
             let exists = Path::new(&path).exists();
@@ -254,8 +581,23 @@ fn generate_bindings(ffmpeg_include_dir: &Path, headers: &[PathBuf]) -> Bindings
             }
             exists
         })
-        .fold(
-            {
+        .collect();
+    for header in &headers {
+        println!("cargo:rerun-if-changed={header}");
+    }
+    let clang_args: Vec<String> = cross_clang_args(env_vars)
+        .into_iter()
+        // Headers of the system hardware acceleration APIs
+        .chain(hw_include_dirs.iter().map(|dir| format!("-I{dir}")))
+        .collect();
+
+    let cache = env_vars.ffmpeg_binding_cache_dir.as_ref().map(|dir| {
+        binding_cache::Entry::new(env_vars, dir, ffmpeg_include_dir, &headers, &clang_args)
+    });
+    let bindings = match cache.as_ref().and_then(|entry| entry.load()) {
+        Some(bindings) => bindings,
+        None => {
+            let builder = headers.iter().fold(
                 bindgen::builder()
                     // Force impl Debug if possible(for `AVCodecParameters`)
                     .impl_debug(true)
@@ -263,22 +605,1109 @@ fn generate_bindings(ffmpeg_include_dir: &Path, headers: &[PathBuf]) -> Bindings
                     .parse_callbacks(Box::new(filter_callback))
                     // Add clang path, for `#include` header finding in bindgen process.
                     .clang_arg(format!("-I{}", ffmpeg_include_dir))
+                    // Use the ABI of the target rather than the host's when cross compiling.
+                    .clang_args(&clang_args)
                     // Workaround: https://github.com/rust-lang/rust-bindgen/issues/2159
                     .blocklist_type("__mingw_ldbl_type_t")
                     // Stop bindgen from prefixing enums
-                    .prepend_enum_name(false)
-            },
-            |builder, header| builder.header(header),
+                    .prepend_enum_name(false),
+                |builder, header| builder.header(header.as_str()),
+            );
+            let builder = if cfg!(feature = "typed_enums") {
+                TYPED_ENUMS
+                    .iter()
+                    .fold(builder, |builder, name| builder.newtype_global_enum(name))
+            } else {
+                builder
+            };
+            let builder = match env_vars.ffmpeg_bindgen_allowlist.as_ref() {
+                Some(allowlist) => {
+                    let items = allowlist
+                        .split_whitespace()
+                        .chain(REQUIRED_ITEMS.iter().copied());
+                    #[cfg(feature = "verify_headers")]
+                    let items = items.chain(header_probe::STRUCTS.iter().map(|(name, _)| *name));
+                    items.fold(builder, |builder, item| builder.allowlist_item(item))
+                }
+                None => builder,
+            };
+            let builder = env_vars
+                .ffmpeg_bindgen_blocklist
+                .iter()
+                .flat_map(|blocklist| blocklist.split_whitespace())
+                .fold(builder, |builder, item| builder.blocklist_item(item));
+            #[cfg(feature = "wrap_static_fns")]
+            let builder = static_fns::configure(builder, env_vars);
+            let bindings = builder
+                .generate()
+                .expect("Binding generation failed.")
+                .to_string();
+            if let Some(entry) = cache.as_ref() {
+                entry.store(&bindings);
+            }
+            bindings
+        }
+    };
+    #[cfg(feature = "wrap_static_fns")]
+    static_fns::compile(env_vars, ffmpeg_include_dir, &hw_include_dirs);
+    bindings
+}
+
+/// Cache of the generated bindings in `FFMPEG_BINDING_CACHE_DIR`, so identical
+/// FFmpeg installs skip bindgen(and libclang).
+mod binding_cache {
+    use super::*;
+
+    /// Keep in sync with the bindgen version in Cargo.toml.
+    const BINDGEN_VERSION: &str = "0.72";
+
+    /// 64-bit FNV-1a, which is stable across Rust versions unlike
+    /// `DefaultHasher`.
+    struct Hasher(u64);
+
+    impl Hasher {
+        fn write(&mut self, bytes: impl AsRef<[u8]>) {
+            let bytes = bytes.as_ref();
+            // Length prefix, so that `("ab", "c")` and `("a", "bc")` differ.
+            for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+                self.0 ^= u64::from(*byte);
+                self.0 = self.0.wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+
+    pub struct Entry {
+        dir: PathBuf,
+        /// C shim generated by bindgen along with the bindings.
+        static_fns_source: Option<PathBuf>,
+    }
+
+    impl Entry {
+        /// The key is a hash of the header contents, the enabled features,
+        /// the target, the clang arguments, the allowlist/blocklist and the
+        /// bindgen version. With `wrap_static_fns`, the include dir is hashed
+        /// too since the C shim includes the headers by absolute paths.
+        pub fn new(
+            env_vars: &EnvVars,
+            cache_dir: &Path,
+            ffmpeg_include_dir: &Path,
+            headers: &[PathBuf],
+            clang_args: &[String],
+        ) -> Self {
+            let mut hasher = Hasher(0xcbf29ce484222325);
+            hasher.write(env!("CARGO_PKG_VERSION"));
+            hasher.write(BINDGEN_VERSION);
+            let mut features: Vec<String> = env::vars()
+                .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+                .collect();
+            features.sort();
+            features.iter().for_each(|feature| hasher.write(feature));
+            let target = env_vars.target.clone().unwrap_or_default();
+            hasher.write(&target);
+            clang_args.iter().for_each(|arg| hasher.write(arg));
+            for list in [
+                &env_vars.ffmpeg_bindgen_allowlist,
+                &env_vars.ffmpeg_bindgen_blocklist,
+            ] {
+                hasher.write(list.as_deref().unwrap_or_default());
+            }
+            for var in [
+                "BINDGEN_EXTRA_CLANG_ARGS".to_string(),
+                format!("BINDGEN_EXTRA_CLANG_ARGS_{target}"),
+                format!("BINDGEN_EXTRA_CLANG_ARGS_{}", target.replace('-', "_")),
+            ] {
+                hasher.write(env::var(var).unwrap_or_default());
+            }
+            for header in headers {
+                // Relative paths, so FFmpeg installed in other places hits.
+                hasher.write(
+                    header
+                        .strip_prefix(ffmpeg_include_dir)
+                        .unwrap_or(header)
+                        .as_str(),
+                );
+                hasher.write(fs::read(header).expect("Cannot read header."));
+            }
+            #[cfg(feature = "wrap_static_fns")]
+            hasher.write(ffmpeg_include_dir.as_str());
+            #[cfg(feature = "wrap_static_fns")]
+            let static_fns_source = Some(static_fns::source_path(env_vars).with_extension("c"));
+            #[cfg(not(feature = "wrap_static_fns"))]
+            let static_fns_source = None;
+            Self {
+                dir: cache_dir.join(format!("{:016x}", hasher.0)),
+                static_fns_source,
+            }
+        }
+
+        pub fn load(&self) -> Option<String> {
+            let bindings = fs::read_to_string(self.dir.join("binding.rs")).ok()?;
+            if let Some(source) = self.static_fns_source.as_ref() {
+                // The bindings are useless without the shim they call.
+                fs::copy(self.dir.join("static_fns.c"), source).ok()?;
+            }
+            Some(bindings)
+        }
+
+        pub fn store(&self, bindings: &str) {
+            // Write to a temporary directory then rename, so that concurrent
+            // builds never see an incomplete entry.
+            let tmp_dir = self
+                .dir
+                .with_extension(format!("tmp{}", std::process::id()));
+            let result = fs::create_dir_all(&tmp_dir)
+                .and_then(|_| fs::write(tmp_dir.join("binding.rs"), bindings))
+                .and_then(|_| match self.static_fns_source.as_ref() {
+                    Some(source) if source.exists() => {
+                        fs::copy(source, tmp_dir.join("static_fns.c")).map(|_| ())
+                    }
+                    _ => Ok(()),
+                })
+                .and_then(|_| fs::rename(&tmp_dir, &self.dir));
+            if let Err(e) = result {
+                let _ = fs::remove_dir_all(&tmp_dir);
+                if !self.dir.exists() {
+                    println!(
+                        "cargo:warning=rusty_ffmpeg: Cannot cache the bindings in `{}`: {e}",
+                        self.dir
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Generate bindings of the FFmpeg headers and write them to `output_binding_path`.
+fn write_bindings(env_vars: &EnvVars, ffmpeg_include_dir: &Path, output_binding_path: &Path) {
+    let bindings = generate_bindings(env_vars, ffmpeg_include_dir, &HEADERS);
+    #[cfg(feature = "wrap_static_fns")]
+    let bindings = static_fns::aliases(&bindings) + &bindings;
+    // The prebuilt binding overwrites the generated one later, which needs the
+    // hand-written macros.
+    let bindings = if cfg!(feature = "use_prebuilt_binding") {
+        bindings
+    } else {
+        println!("cargo:rustc-cfg=rusty_ffmpeg_header_macros");
+        let macros = header_macros::generate(ffmpeg_include_dir, &bindings);
+        #[cfg(feature = "verify_headers")]
+        let _ = header_probe::GENERATED_MACROS.set(macros.clone());
+        macros + &bindings
+    };
+    fs::write(output_binding_path, bindings).expect("Cannot write binding to file.");
+}
+
+/// C compiler configured for the FFmpeg headers.
+#[cfg(any(feature = "wrap_static_fns", feature = "verify_headers"))]
+fn c_build(env_vars: &EnvVars, ffmpeg_include_dir: &Path) -> cc::Build {
+    let mut build = cc::Build::new();
+    build
+        .include(ffmpeg_include_dir)
+        // FFmpeg headers have a lot of deprecated declarations.
+        .warnings(false);
+    if let Some(ffmpeg_sysroot) = env_vars.ffmpeg_sysroot.as_ref() {
+        build.flag(format!("--sysroot={ffmpeg_sysroot}"));
+    }
+    build
+}
+
+/// Rust equivalents of the `AV_PIX_FMT_NE(...)`, `AV_CH_*` and
+/// `AV_CHANNEL_LAYOUT_*` macros, which bindgen skips since they are not
+/// literals. The hand-written ones in `src/avutil` are only used with prebuilt
+/// bindings.
+mod header_macros {
+    use super::*;
+    use std::fmt::Write;
+
+    /// `(name, body)` of the object-like `#define`s in a header.
+    fn defines(header: &str) -> Vec<(String, String)> {
+        let header = header.replace("\\\r\n", " ").replace("\\\n", " ");
+        // Remove comments
+        let mut code = String::new();
+        let mut rest = header.as_str();
+        while let Some(start) = rest.find("/*") {
+            code.push_str(&rest[..start]);
+            rest = rest[start..]
+                .find("*/")
+                .map_or("", |end| &rest[start + end + 2..]);
+        }
+        code.push_str(rest);
+
+        code.lines()
+            .map(|line| line.split("//").next().unwrap().trim())
+            .filter_map(|line| line.strip_prefix('#'))
+            .filter_map(|line| line.trim_start().strip_prefix("define"))
+            .filter(|line| line.starts_with([' ', '\t']))
+            .filter_map(|line| {
+                let line = line.trim_start();
+                let end = line
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(line.len());
+                let (name, body) = line.split_at(end);
+                // Skip function-like macros
+                (!name.is_empty() && !body.starts_with('('))
+                    .then(|| (name.to_string(), body.trim().to_string()))
+            })
+            .collect()
+    }
+
+    fn is_ident(s: &str) -> bool {
+        s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    fn is_number(s: &str) -> bool {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+    }
+
+    /// Arguments of `body` if it's a `function(...)` call.
+    fn call_args<'a>(body: &'a str, function: &str) -> Option<Vec<&'a str>> {
+        let args = body
+            .strip_prefix(function)?
+            .trim_start()
+            .strip_prefix('(')?
+            .strip_suffix(')')?;
+        Some(args.split(',').map(str::trim).collect())
+    }
+
+    /// `AV_CH_*` masks, e.g. `(1ULL << AV_CHAN_FRONT_LEFT)` or
+    /// `(AV_CH_LAYOUT_STEREO|AV_CH_LOW_FREQUENCY)`.
+    fn channel_mask(body: &str, known: &HashSet<String>) -> Option<String> {
+        let body = body.trim_start_matches('(').trim_end_matches(')').trim();
+        if let Some(channel) = body.strip_prefix("1ULL") {
+            let channel = channel.trim_start().strip_prefix("<<")?.trim();
+            return known
+                .contains(channel)
+                .then(|| format!("1u64 << ({channel} as u64)"));
+        }
+        let masks: Vec<&str> = body.split('|').map(str::trim).collect();
+        masks
+            .iter()
+            .all(|mask| is_ident(mask) && known.contains(*mask))
+            .then(|| masks.join(" | "))
+    }
+
+    /// `AV_CHANNEL_LAYOUT_*` initializers, e.g. `AV_CHANNEL_LAYOUT_MASK(2,
+    /// AV_CH_LAYOUT_STEREO)` or `{ AV_CHANNEL_ORDER_AMBISONIC, 4, { 0 }, NULL }`.
+    fn channel_layout(body: &str, known: &HashSet<String>) -> Option<String> {
+        let layout = |order: &str, nb_channels: &str, mask: &str| {
+            format!(
+                "AVChannelLayout {{ order: {order}, nb_channels: {nb_channels}, u: AVChannelLayout__bindgen_ty_1 {{ mask: {mask} }}, opaque: ::std::ptr::null_mut() }}"
+            )
+        };
+        if is_ident(body) {
+            return known.contains(body).then(|| body.to_string());
+        }
+        if let Some(args) = call_args(body, "AV_CHANNEL_LAYOUT_MASK") {
+            return match args[..] {
+                [nb_channels, mask] if is_number(nb_channels) && known.contains(mask) => {
+                    Some(layout("AV_CHANNEL_ORDER_NATIVE", nb_channels, mask))
+                }
+                _ => None,
+            };
+        }
+        let fields = body.strip_prefix('{')?.replace(['{', '}'], "");
+        match fields.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+            [order, nb_channels, mask, "NULL"]
+                if known.contains(order) && is_number(nb_channels) && is_number(mask) =>
+            {
+                Some(layout(order, nb_channels, mask))
+            }
+            _ => None,
+        }
+    }
+
+    /// Split the arguments of `all(...)`, `any(...)` or `not(...)` at the
+    /// top-level commas.
+    fn cfg_arguments(arguments: &str) -> Vec<&str> {
+        let mut result = Vec::new();
+        let (mut depth, mut start) = (0, 0);
+        for (i, c) in arguments.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    result.push(arguments[start..i].trim());
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        result.push(arguments[start..].trim());
+        result.retain(|x| !x.is_empty());
+        result
+    }
+
+    /// Evaluate a `#[cfg(...)]` predicate of the crate features and the
+    /// target, panics on the ones it doesn't know, e.g. the cfgs set by this
+    /// script, rather than probing the wrong constants.
+    fn cfg_enabled(predicate: &str) -> bool {
+        let predicate = predicate.trim();
+        let arguments = |operator: &str| {
+            predicate
+                .strip_prefix(operator)
+                .and_then(|x| x.strip_suffix(')'))
+                .map(cfg_arguments)
+        };
+        if let Some(arguments) = arguments("all(") {
+            return arguments.into_iter().all(cfg_enabled);
+        }
+        if let Some(arguments) = arguments("any(") {
+            return arguments.into_iter().any(cfg_enabled);
+        }
+        if let Some([argument]) = arguments("not(").as_deref() {
+            return !cfg_enabled(argument);
+        }
+        if predicate == "test" {
+            return false;
+        }
+        let key_value = predicate.split_once('=').and_then(|(key, value)| {
+            let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+            Some((key.trim(), value))
+        });
+        match key_value {
+            Some(("feature", feature)) => env::var_os(format!(
+                "CARGO_FEATURE_{}",
+                feature.to_uppercase().replace('-', "_")
+            ))
+            .is_some(),
+            Some((key, value)) if key.starts_with("target_") => {
+                env::var(format!("CARGO_CFG_{}", key.to_uppercase()))
+                    .is_ok_and(|x| x.split(',').any(|x| x == value))
+            }
+            _ => panic!("Unsupported `#[cfg({predicate})]` in hand-written constants."),
+        }
+    }
+
+    /// Names of the constants in the hand-written `src/avutil` files enabled
+    /// by the crate features.
+    pub fn hand_written_constants(file: &str) -> Vec<String> {
+        let code = fs::read_to_string(file).expect("Cannot read hand-written constants.");
+        let mut enabled = true;
+        let mut names = Vec::new();
+        for line in code.lines().map(str::trim) {
+            if let Some(predicate) = line.strip_prefix("#[cfg(") {
+                let predicate = predicate
+                    .strip_suffix(")]")
+                    .unwrap_or_else(|| panic!("Unsupported `{line}` in `{file}`."));
+                enabled &= cfg_enabled(predicate);
+                continue;
+            }
+            let name = [
+                "AV_PIX_FMT_NE!(",
+                "AV_CH_MASK_CONST!(",
+                "AV_CH_LAYOUT_CONST!(",
+                "pub const ",
+            ]
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix))
+            .and_then(|x| x.split([',', ':']).next())
+            .map(str::trim);
+            if let Some(name) = name {
+                if enabled
+                    && ["AVERROR_", "AV_CH", "AV_PIX_FMT_"]
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+                {
+                    names.push(name.to_string());
+                }
+                enabled = true;
+            }
+        }
+        names
+    }
+
+    /// Generate the constants missing in `bindings`, macros referring to
+    /// unknown names are skipped.
+    pub fn generate(ffmpeg_include_dir: &Path, bindings: &str) -> String {
+        let read = |header: &str| {
+            fs::read_to_string(ffmpeg_include_dir.join(header))
+                .map(|x| defines(&x))
+                .unwrap_or_default()
+        };
+        let mut known: HashSet<String> = bindings
+            .lines()
+            .filter_map(|line| line.trim_start().strip_prefix("pub const "))
+            .filter_map(|line| line.split(':').next())
+            .map(str::to_string)
+            .collect();
+        let mut code = String::new();
+
+        for (name, body) in read("libavutil/pixfmt.h") {
+            if known.contains(&name) {
+                continue;
+            }
+            if let Some([be, le]) = call_args(&body, "AV_PIX_FMT_NE").as_deref() {
+                let (be, le) = (format!("AV_PIX_FMT_{be}"), format!("AV_PIX_FMT_{le}"));
+                if known.contains(&be) && known.contains(&le) {
+                    writeln!(
+                        code,
+                        "#[cfg(target_endian = \"big\")]\npub const {name}: AVPixelFormat = {be};"
+                    )
+                    .unwrap();
+                    writeln!(code, "#[cfg(target_endian = \"little\")]\npub const {name}: AVPixelFormat = {le};").unwrap();
+                    known.insert(name);
+                }
+            }
+        }
+
+        let has_channel_layout = bindings.contains("pub struct AVChannelLayout {");
+        for (name, body) in read("libavutil/channel_layout.h") {
+            if known.contains(&name) {
+                continue;
+            }
+            let constant = if name.starts_with("AV_CH_") {
+                channel_mask(&body, &known).map(|mask| format!("pub const {name}: u64 = {mask};"))
+            } else if name.starts_with("AV_CHANNEL_LAYOUT_") && has_channel_layout {
+                channel_layout(&body, &known)
+                    .map(|layout| format!("pub const {name}: AVChannelLayout = {layout};"))
+            } else {
+                None
+            };
+            if let Some(constant) = constant {
+                writeln!(code, "{constant}").unwrap();
+                known.insert(name);
+            }
+        }
+
+        // The hand-written ones replace these with prebuilt bindings, so the
+        // macros they port should be generated too.
+        let mut hand_written = hand_written_constants("src/avutil/pixfmt.rs");
+        if env::var_os("CARGO_FEATURE_FFMPEG6").is_some() {
+            hand_written.extend(hand_written_constants("src/avutil/channel_layout.rs"));
+        }
+        for name in hand_written.iter().filter(|name| !known.contains(*name)) {
+            println!(
+                "cargo:warning=rusty_ffmpeg: `{name}` in `src/avutil` is not generated from the FFmpeg headers."
+            );
+        }
+        code
+    }
+}
+
+/// Bindings of the `static inline` functions in FFmpeg headers, which are
+/// compiled into a C shim since they don't exist in the FFmpeg libraries.
+#[cfg(feature = "wrap_static_fns")]
+mod static_fns {
+    use super::*;
+
+    /// Functions ported to Rust by hand in `src/avutil`, they are kept since
+    /// they are safe(and `const`) while the shim functions are not, and the
+    /// shim would otherwise be glob imported alongside them.
+    const HAND_PORTED: [&str; 5] = [
+        "av_make_q",
+        "av_cmp_q",
+        "av_q2d",
+        "av_inv_q",
+        "av_make_error_string",
+    ];
+
+    pub fn source_path(env_vars: &EnvVars) -> PathBuf {
+        // bindgen appends the `.c` extension.
+        env_vars.out_dir.as_ref().unwrap().join("static_fns")
+    }
+
+    pub fn configure(builder: bindgen::Builder, env_vars: &EnvVars) -> bindgen::Builder {
+        HAND_PORTED.iter().fold(
+            builder
+                .wrap_static_fns(true)
+                .wrap_static_fns_path(source_path(env_vars)),
+            |builder, name| builder.blocklist_function(name),
         )
-        .generate()
-        .expect("Binding generation failed.")
+    }
+
+    /// Compile the C shim generated by bindgen, `cc` emits the linking
+    /// instructions.
+    pub fn compile(env_vars: &EnvVars, ffmpeg_include_dir: &Path, include_dirs: &[PathBuf]) {
+        let source = source_path(env_vars).with_extension("c");
+        if !source.exists() {
+            return;
+        }
+        c_build(env_vars, ffmpeg_include_dir)
+            .file(&source)
+            .includes(include_dirs)
+            .compile("rusty_ffmpeg_static_fns");
+    }
+
+    /// FFmpeg headers define portable implementations such as
+    /// `av_clip_uintp2_c`, and `#define av_clip_uintp2 av_clip_uintp2_c` unless
+    /// an architecture specific one exists, which bindgen can't see. Alias them
+    /// back to the names used in C.
+    pub fn aliases(bindings: &str) -> String {
+        let functions: HashSet<&str> = bindings
+            .lines()
+            .filter_map(|line| line.trim_start().strip_prefix("pub fn "))
+            .filter_map(|line| line.split('(').next())
+            .collect();
+        let mut aliases: Vec<String> = functions
+            .iter()
+            .filter(|name| name.starts_with("av_"))
+            .filter_map(|name| name.strip_suffix("_c").map(|alias| (name, alias)))
+            .filter(|(_, alias)| !functions.contains(alias))
+            .map(|(name, alias)| format!("pub use self::{name} as {alias};\n"))
+            .collect();
+        aliases.sort();
+        aliases.concat()
+    }
 }
 
-fn linking_with_libs_dir(library_names: &[&str], ffmpeg_libs_dir: &Path, mode: FFmpegLinkMode) {
+/// Values of the C headers compared with the Rust side by the test in
+/// `src/header_probe.rs`: struct layouts and the macros ported by hand or
+/// generated by `header_macros`.
+#[cfg(feature = "verify_headers")]
+mod header_probe {
+    use super::*;
+    use once_cell::sync::OnceCell;
+    use std::fmt::Write;
+
+    /// The FFmpeg include dir found by the linking method.
+    pub static INCLUDE_DIR: OnceCell<PathBuf> = OnceCell::new();
+    /// Set if `header_macros` generated the macros rather than `src/avutil`.
+    pub static GENERATED_MACROS: OnceCell<String> = OnceCell::new();
+
+    /// Fields available in all supported FFmpeg versions.
+    pub const STRUCTS: [(&str, &[&str]); 5] = [
+        (
+            "AVFrame",
+            &[
+                "data",
+                "linesize",
+                "extended_data",
+                "width",
+                "height",
+                "nb_samples",
+                "format",
+                "sample_aspect_ratio",
+                "pts",
+                "pkt_dts",
+                "sample_rate",
+                "buf",
+                "extended_buf",
+                "nb_extended_buf",
+                "side_data",
+                "nb_side_data",
+                "flags",
+                "hw_frames_ctx",
+                "opaque_ref",
+            ],
+        ),
+        (
+            "AVPacket",
+            &[
+                "buf",
+                "pts",
+                "dts",
+                "data",
+                "size",
+                "stream_index",
+                "flags",
+                "side_data",
+                "side_data_elems",
+                "duration",
+                "pos",
+            ],
+        ),
+        (
+            "AVCodecContext",
+            &[
+                "av_class",
+                "codec_type",
+                "codec",
+                "codec_id",
+                "codec_tag",
+                "priv_data",
+                "bit_rate",
+                "flags",
+                "extradata",
+                "extradata_size",
+                "time_base",
+                "width",
+                "height",
+                "pix_fmt",
+                "sample_rate",
+                "sample_fmt",
+                "hw_frames_ctx",
+                "hw_device_ctx",
+            ],
+        ),
+        (
+            "AVFormatContext",
+            &[
+                "av_class",
+                "iformat",
+                "oformat",
+                "priv_data",
+                "pb",
+                "ctx_flags",
+                "nb_streams",
+                "streams",
+                "url",
+                "start_time",
+                "duration",
+                "bit_rate",
+            ],
+        ),
+        // Since FFmpeg 5.1, checked only if the headers have it.
+        ("AVChannelLayout", &["order", "nb_channels", "u", "opaque"]),
+    ];
+
+    /// Write `header_probe.c` with the C values and `header_probe.rs` with the
+    /// Rust values, the latter is empty if no FFmpeg headers are found.
+    pub fn generate(env_vars: &EnvVars) {
+        let out_dir = env_vars.out_dir.as_ref().unwrap();
+        let Some(ffmpeg_include_dir) = INCLUDE_DIR.get() else {
+            fs::write(
+                out_dir.join("header_probe.rs"),
+                "fn header_probe() -> Vec<(&'static str, u64, Option<u64>)> {\n    Vec::new()\n}\n",
+            )
+            .expect("Cannot write header probe.");
+            return;
+        };
+
+        let mut constants = header_macros::hand_written_constants("src/avutil/error.rs");
+        match GENERATED_MACROS.get() {
+            Some(macros) => constants.extend(
+                macros
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("pub const "))
+                    .filter_map(|line| line.split(':').next())
+                    .map(str::to_string),
+            ),
+            None => {
+                constants.extend(header_macros::hand_written_constants(
+                    "src/avutil/pixfmt.rs",
+                ));
+                if env::var_os("CARGO_FEATURE_FFMPEG6").is_some() {
+                    constants.extend(header_macros::hand_written_constants(
+                        "src/avutil/channel_layout.rs",
+                    ));
+                }
+            }
+        }
+        // The layouts are structs, which can't be compared as integers.
+        constants.retain(|name| !name.starts_with("AV_CHANNEL_LAYOUT_"));
+        constants.sort_unstable();
+        constants.dedup();
+
+        let has_channel_layout =
+            fs::read_to_string(ffmpeg_include_dir.join("libavutil/channel_layout.h"))
+                .is_ok_and(|x| x.contains("AVChannelLayout {"));
+
+        let mut c = String::from(
+            "#include <stddef.h>
+#include <libavutil/channel_layout.h>
+#include <libavutil/error.h>
+#include <libavutil/frame.h>
+#include <libavutil/pixfmt.h>
+#include <libavcodec/avcodec.h>
+#include <libavformat/avformat.h>
+
+const struct {
+    unsigned long long value;
+    int defined;
+} rusty_ffmpeg_header_probe[] = {
+",
+        );
+        // The entries of the `vec!` in `header_probe()`.
+        let mut values = String::new();
+        let mut items = 0;
+        let mut item = |name: &str, c_value: &str, rust_value: &str, ifdef: bool| {
+            if ifdef {
+                writeln!(
+                    c,
+                    "#ifdef {name}\n    {{ {c_value}, 1 }},\n#else\n    {{ 0, 0 }},\n#endif"
+                )
+                .unwrap();
+            } else {
+                writeln!(c, "    {{ {c_value}, 1 }},").unwrap();
+            }
+            writeln!(
+                values,
+                "        (\"{name}\", {rust_value} as u64, c[{items}].value()),"
+            )
+            .unwrap();
+            items += 1;
+        };
+        for (name, fields) in STRUCTS {
+            if name == "AVChannelLayout" && !has_channel_layout {
+                continue;
+            }
+            item(
+                &format!("sizeof({name})"),
+                &format!("sizeof({name})"),
+                &format!("std::mem::size_of::<ffi::{name}>()"),
+                false,
+            );
+            for field in fields {
+                item(
+                    &format!("offsetof({name}, {field})"),
+                    &format!("offsetof({name}, {field})"),
+                    &format!("std::mem::offset_of!(ffi::{name}, {field})"),
+                    false,
+                );
+            }
+        }
+        for name in &constants {
+            let rust_value = if cfg!(feature = "typed_enums") && name.starts_with("AV_PIX_FMT_") {
+                format!("ffi::{name}.0")
+            } else {
+                format!("ffi::{name}")
+            };
+            item(
+                name,
+                &format!("(unsigned long long)({name})"),
+                &rust_value,
+                true,
+            );
+        }
+        c.push_str("};\n");
+        let rust = format!(
+            "use crate::ffi;
+use std::ffi::c_int;
+
+#[repr(C)]
+struct HeaderProbeItem {{
+    value: u64,
+    defined: c_int,
+}}
+
+impl HeaderProbeItem {{
+    fn value(&self) -> Option<u64> {{
+        (self.defined != 0).then_some(self.value)
+    }}
+}}
+
+#[link(name = \"rusty_ffmpeg_header_probe\", kind = \"static\")]
+extern \"C\" {{
+    static rusty_ffmpeg_header_probe: [HeaderProbeItem; {items}];
+}}
+
+#[allow(clippy::unnecessary_cast)]
+fn header_probe() -> Vec<(&'static str, u64, Option<u64>)> {{
+    let c = unsafe {{ &rusty_ffmpeg_header_probe }};
+    vec![
+{values}    ]
+}}
+"
+        );
+
+        let source = out_dir.join("header_probe.c");
+        fs::write(&source, c).expect("Cannot write header probe.");
+        fs::write(out_dir.join("header_probe.rs"), rust).expect("Cannot write header probe.");
+        // The probe is linked by the `#[link]` attribute of the test module
+        // rather than by cargo, so non-test builds don't link it.
+        c_build(env_vars, ffmpeg_include_dir)
+            .file(&source)
+            .cargo_metadata(false)
+            .compile("rusty_ffmpeg_header_probe");
+        println!("cargo:rustc-link-search=native={out_dir}");
+    }
+}
+
+/// Names of the FFmpeg libraries to link, FFmpeg configured with
+/// `--build-suffix=-rf` produces `libavcodec-rf.so` and `libavcodec-rf.pc`.
+fn ffmpeg_libs(env_vars: &EnvVars) -> Vec<String> {
+    let suffix = env_vars.ffmpeg_lib_suffix.as_deref().unwrap_or_default();
+    LIBS.iter().map(|lib| format!("{lib}{suffix}")).collect()
+}
+
+fn linking_with_libs_dir(
+    library_names: &[String],
+    ffmpeg_libs_dir: &Path,
+    mode: FFmpegLinkMode,
+    extra_libs: Option<&str>,
+) {
     println!("cargo:rustc-link-search=native={ffmpeg_libs_dir}");
     for library_name in library_names {
         println!("cargo:rustc-link-lib={mode}={library_name}");
     }
+    // Static FFmpeg libraries don't carry their dependencies, so the
+    // dependencies(e.g. libx264, zlib, libm) need to be linked after them.
+    let dependencies = if let Some(extra_libs) = extra_libs {
+        static_dependencies::parse_link_flags(extra_libs)
+    } else if mode == FFmpegLinkMode::Static {
+        static_dependencies::from_pc_files(library_names, ffmpeg_libs_dir)
+    } else {
+        Vec::new()
+    };
+    for dependency in dependencies {
+        dependency.emit(library_names);
+    }
+}
+
+/// Resolve the transitive dependencies of static FFmpeg libraries.
+mod static_dependencies {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum LinkItem {
+        SearchPath(PathBuf),
+        Lib(String),
+        Framework(String),
+        /// Passed to the linker as is, e.g. `/opt/x264/lib/libx264.a` or
+        /// `-l:libx264.a`, which have no `rustc-link-lib` equivalent.
+        Arg(String),
+    }
+
+    impl LinkItem {
+        /// Print the cargo instruction of this item, FFmpeg libraries
+        /// themselves are skipped since they are already linked.
+        pub fn emit(&self, library_names: &[String]) {
+            match self {
+                LinkItem::SearchPath(path) => println!("cargo:rustc-link-search=native={path}"),
+                LinkItem::Lib(name) => {
+                    if !library_names.contains(name) {
+                        println!("cargo:rustc-link-lib={name}");
+                    }
+                }
+                LinkItem::Framework(name) => println!("cargo:rustc-link-lib=framework={name}"),
+                LinkItem::Arg(arg) => println!("cargo:rustc-link-arg={arg}"),
+            }
+        }
+    }
+
+    /// Parse pkg-config style linker flags, e.g. `-L/opt/x264/lib -lx264 -lz -lm`.
+    /// Library files(e.g. `/opt/x264/lib/libx264.a`) and `-l:libx264.a` are
+    /// passed to the linker as is, other bare words are ignored with a warning.
+    ///
+    /// Duplicated search paths keep their first occurrence, duplicated
+    /// libraries keep their last occurrence, which is the correct order for
+    /// static linking.
+    pub fn parse_link_flags(flags: &str) -> Vec<LinkItem> {
+        let mut items = Vec::new();
+        let mut tokens = flags.split_whitespace();
+        while let Some(token) = tokens.next() {
+            let item = if let Some(path) = token.strip_prefix("-L") {
+                LinkItem::SearchPath(PathBuf::from(path))
+            } else if token.starts_with("-l:") {
+                LinkItem::Arg(token.to_string())
+            } else if let Some(name) = token.strip_prefix("-l") {
+                LinkItem::Lib(name.to_string())
+            } else if token == "-pthread" {
+                LinkItem::Lib("pthread".to_string())
+            } else if token == "-framework" {
+                match tokens.next() {
+                    Some(name) => LinkItem::Framework(name.to_string()),
+                    None => continue,
+                }
+            } else if let Some(name) = token.strip_prefix("-Wl,-framework,") {
+                LinkItem::Framework(name.to_string())
+            } else if token.starts_with('-') {
+                println!("cargo:warning=rusty_ffmpeg: ignored unsupported linker flag `{token}`");
+                continue;
+            } else if is_library_file(token) {
+                LinkItem::Arg(token.to_string())
+            } else {
+                println!("cargo:warning=rusty_ffmpeg: ignored unsupported linker input `{token}`");
+                continue;
+            };
+            items.push(item);
+        }
+
+        let mut result: Vec<LinkItem> = Vec::new();
+        for item in items {
+            match &item {
+                LinkItem::SearchPath(_) if result.contains(&item) => {}
+                LinkItem::SearchPath(_) => result.push(item),
+                LinkItem::Lib(_) | LinkItem::Framework(_) | LinkItem::Arg(_) => {
+                    result.retain(|x| x != &item);
+                    result.push(item);
+                }
+            }
+        }
+        result
+    }
+
+    /// Absolute paths and files with a library extension, including
+    /// versioned shared libraries like `libx264.so.164`.
+    fn is_library_file(token: &str) -> bool {
+        let path = Path::new(token);
+        let file_name = path.file_name().unwrap_or_default();
+        path.is_absolute()
+            || [".a", ".lib", ".dylib", ".so"]
+                .iter()
+                .any(|ext| file_name.ends_with(ext))
+            || file_name.contains(".so.")
+    }
+
+    /// Read the `Libs.private` of the `.pc` files generated by FFmpeg's
+    /// `make install`, which are placed in `{libs_dir}/pkgconfig`.
+    pub fn from_pc_files(library_names: &[String], ffmpeg_libs_dir: &Path) -> Vec<LinkItem> {
+        let mut flags = String::new();
+        let mut missing = Vec::new();
+        for library_name in library_names {
+            let pc_file = [
+                ffmpeg_libs_dir.join("pkgconfig"),
+                ffmpeg_libs_dir.to_path_buf(),
+            ]
+            .into_iter()
+            .map(|dir| dir.join(format!("lib{library_name}.pc")))
+            .find(|path| path.exists());
+            let Some(pc_file) = pc_file else {
+                missing.push(format!("lib{library_name}.pc"));
+                continue;
+            };
+            println!("cargo:rerun-if-changed={pc_file}");
+            if let Some(libs_private) = pc_fields(&pc_file).get("Libs.private") {
+                flags.push(' ');
+                flags.push_str(libs_private);
+            }
+        }
+        if !missing.is_empty() {
+            println!(
+                "cargo:warning=rusty_ffmpeg: {} not found in `{ffmpeg_libs_dir}`, their dependencies won't be linked. \
+                Set `FFMPEG_EXTRA_LIBS` if linking fails.",
+                missing.join(", ")
+            );
+        }
+        parse_link_flags(&flags)
+    }
+
+    /// Returns the keyword fields(e.g. `Libs.private`) of a `.pc` file with
+    /// variables expanded.
+    pub fn pc_fields(pc_file: &Path) -> HashMap<String, String> {
+        let content = fs::read_to_string(pc_file)
+            .unwrap_or_else(|e| panic!("Failed to read `{pc_file}`: {e}"));
+        let mut variables = HashMap::new();
+        let mut fields = HashMap::new();
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let (key, value, is_variable) = match (line.find('='), line.find(':')) {
+                (Some(eq), Some(colon)) if eq < colon => (&line[..eq], &line[eq + 1..], true),
+                (Some(eq), None) => (&line[..eq], &line[eq + 1..], true),
+                (_, Some(colon)) => (&line[..colon], &line[colon + 1..], false),
+                (None, None) => continue,
+            };
+            let value = expand_variables(value.trim(), &variables);
+            if is_variable {
+                variables.insert(key.trim().to_string(), value);
+            } else {
+                fields.insert(key.trim().to_string(), value);
+            }
+        }
+        fields
+    }
+
+    fn expand_variables(value: &str, variables: &HashMap<String, String>) -> String {
+        let mut result = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            let name = &rest[start + 2..start + end];
+            result.push_str(variables.get(name).map(String::as_str).unwrap_or_default());
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_parse_link_flags() {
+            assert_eq!(
+                parse_link_flags("-L/opt/x264/lib -lx264 -pthread -lm -L/opt/x264/lib -lz -lm"),
+                vec![
+                    LinkItem::SearchPath(PathBuf::from("/opt/x264/lib")),
+                    LinkItem::Lib("x264".to_string()),
+                    LinkItem::Lib("pthread".to_string()),
+                    LinkItem::Lib("z".to_string()),
+                    LinkItem::Lib("m".to_string()),
+                ]
+            );
+            assert_eq!(
+                parse_link_flags("-framework CoreVideo -Wl,-framework,VideoToolbox"),
+                vec![
+                    LinkItem::Framework("CoreVideo".to_string()),
+                    LinkItem::Framework("VideoToolbox".to_string()),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_parse_link_flags_files() {
+            assert_eq!(
+                parse_link_flags(
+                    "-l:libx264.a /opt/x264/lib/libx264.a libz.so.1 dav1d.lib libdrm.so -lvpx bogus"
+                ),
+                vec![
+                    LinkItem::Arg("-l:libx264.a".to_string()),
+                    LinkItem::Arg("/opt/x264/lib/libx264.a".to_string()),
+                    LinkItem::Arg("libz.so.1".to_string()),
+                    LinkItem::Arg("dav1d.lib".to_string()),
+                    LinkItem::Arg("libdrm.so".to_string()),
+                    LinkItem::Lib("vpx".to_string()),
+                ]
+            );
+            assert!(parse_link_flags("-Wl,--as-needed -rdynamic").is_empty());
+        }
+    }
+}
+
+/// Version of a FFmpeg library read from its `version_major.h` and
+/// `version.h`, e.g. `62.28.100`.
+fn library_version(ffmpeg_include_dir: &Path, library_name: &str) -> Option<String> {
+    let prefix = format!("LIB{}_VERSION_", library_name.to_uppercase());
+    let mut content = String::new();
+    for header in ["version_major.h", "version.h"] {
+        let path = ffmpeg_include_dir
+            .join(format!("lib{library_name}"))
+            .join(header);
+        if let Ok(header) = fs::read_to_string(path) {
+            content.push_str(&header);
+        }
+    }
+    let component = |name: &str| {
+        content.lines().find_map(|line| {
+            let mut tokens = line.split_whitespace();
+            (tokens.next() == Some("#define") && tokens.next() == Some(&*format!("{prefix}{name}")))
+                .then(|| tokens.next())
+                .flatten()
+                .and_then(|x| x.parse::<u32>().ok())
+        })
+    };
+    Some(format!(
+        "{}.{}.{}",
+        component("MAJOR")?,
+        component("MINOR")?,
+        component("MICRO")?
+    ))
+}
+
+/// Export the linked FFmpeg to dependent crates through the `links = "ffmpeg"`
+/// metadata, which are available as `DEP_FFMPEG_INCLUDE`,
+/// `DEP_FFMPEG_LIB_DIR`, `DEP_FFMPEG_LINK_MODE` and
+/// `DEP_FFMPEG_{LIBRARY}_VERSION` in their build scripts.
+fn emit_links_metadata(
+    ffmpeg_include_dir: Option<&Path>,
+    ffmpeg_lib_dir: Option<&Path>,
+    mode: FFmpegLinkMode,
+) {
+    if let Some(ffmpeg_include_dir) = ffmpeg_include_dir {
+        println!("cargo:include={ffmpeg_include_dir}");
+        #[cfg(feature = "verify_headers")]
+        let _ = header_probe::INCLUDE_DIR.set(ffmpeg_include_dir.to_path_buf());
+        for library_name in LIBS.iter() {
+            if let Some(version) = library_version(ffmpeg_include_dir, library_name) {
+                println!("cargo:{library_name}_version={version}");
+            }
+        }
+    }
+    if let Some(ffmpeg_lib_dir) = ffmpeg_lib_dir {
+        println!("cargo:lib_dir={ffmpeg_lib_dir}");
+    }
+    let mode = match mode {
+        FFmpegLinkMode::Static => "static",
+        FFmpegLinkMode::Dynamic => "dynamic",
+    };
+    println!("cargo:link_mode={mode}");
 }
 
 fn linking_with_single_lib(library_name: &str, ffmpeg_lib_dir: &Path, mode: FFmpegLinkMode) {
@@ -286,6 +1715,115 @@ fn linking_with_single_lib(library_name: &str, ffmpeg_lib_dir: &Path, mode: FFmp
     println!("cargo:rustc-link-lib={mode}={library_name}");
 }
 
+/// Let the binaries find dynamically linked FFmpeg libraries outside the
+/// system paths at runtime, with `FFMPEG_RPATH` and `FFMPEG_BUNDLE_LIBS`.
+mod runtime_libs {
+    use super::*;
+
+    fn target_os() -> String {
+        env::var("CARGO_CFG_TARGET_OS").unwrap_or_default()
+    }
+
+    /// If `file_name` is a runtime file of the shared library, e.g.
+    /// `libavcodec.so.62`, `libavcodec.62.dylib` or `avcodec-62.dll`.
+    fn is_shared_lib(file_name: &str, library_name: &str, target_os: &str) -> bool {
+        match target_os {
+            "windows" => file_name
+                .strip_prefix(library_name)
+                .and_then(|x| x.strip_suffix(".dll"))
+                .is_some_and(|x| x.is_empty() || x.starts_with('-')),
+            "macos" | "ios" => file_name
+                .strip_prefix(&format!("lib{library_name}"))
+                .and_then(|x| x.strip_suffix(".dylib"))
+                .is_some_and(|x| x.is_empty() || x.starts_with('.')),
+            _ => file_name
+                .strip_prefix(&format!("lib{library_name}.so"))
+                .is_some_and(|x| x.is_empty() || x.starts_with('.')),
+        }
+    }
+
+    /// `target/<profile>`, where the binaries are placed.
+    fn target_profile_dir(env_vars: &EnvVars) -> Option<PathBuf> {
+        // OUT_DIR is `target/<profile>/build/<package>-<hash>/out`
+        let out_dir = env_vars.out_dir.as_ref()?;
+        let build_dir = out_dir.ancestors().nth(2)?;
+        (build_dir.file_name() == Some("build")).then(|| build_dir.parent().unwrap().to_path_buf())
+    }
+
+    /// Copy the shared libraries, SONAME symlinks(e.g. `libavcodec.so.62 ->
+    /// libavcodec.so.62.28.100`) are kept as symlinks if possible.
+    fn bundle(library_names: &[String], ffmpeg_lib_dir: &Path, dest_dir: &Path) {
+        let target_os = target_os();
+        let entries = fs::read_dir(ffmpeg_lib_dir)
+            .unwrap_or_else(|e| panic!("Cannot read `{ffmpeg_lib_dir}`: {e}"));
+        let mut bundled = 0;
+        for entry in entries.filter_map(Result::ok) {
+            let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if !library_names
+                .iter()
+                .any(|library_name| is_shared_lib(&file_name, library_name, &target_os))
+            {
+                continue;
+            }
+            let dest = dest_dir.join(&file_name);
+            let _ = fs::remove_file(&dest);
+            #[cfg(unix)]
+            if let Ok(link) = fs::read_link(entry.path()) {
+                // Links to the files in the same directory
+                if link.parent() == Some(std::path::Path::new(""))
+                    && std::os::unix::fs::symlink(&link, &dest).is_ok()
+                {
+                    bundled += 1;
+                    continue;
+                }
+            }
+            fs::copy(entry.path(), &dest)
+                .unwrap_or_else(|e| panic!("Cannot copy `{file_name}` to `{dest_dir}`: {e}"));
+            bundled += 1;
+        }
+        if bundled == 0 {
+            println!(
+                "cargo:warning=rusty_ffmpeg: No shared FFmpeg library found in `{ffmpeg_lib_dir}` to bundle."
+            );
+        }
+    }
+
+    /// Called when FFmpeg is linked dynamically from `ffmpeg_lib_dir`.
+    pub fn setup(env_vars: &EnvVars, library_names: &[String], ffmpeg_lib_dir: &Path) {
+        let target_os = target_os();
+        if let Some(rpath) = env_vars.ffmpeg_rpath.as_deref() {
+            let rpath = match rpath {
+                "absolute" => ffmpeg_lib_dir.to_string(),
+                "origin" if matches!(&*target_os, "macos" | "ios") => "@loader_path".to_string(),
+                "origin" => "$ORIGIN".to_string(),
+                rpath => rpath.to_string(),
+            };
+            if target_os == "windows" {
+                println!("cargo:warning=rusty_ffmpeg: FFMPEG_RPATH is ignored on Windows.");
+            } else {
+                // Only applies to the binaries of this package, dependent
+                // crates get it as `DEP_FFMPEG_RPATH`.
+                println!("cargo:rustc-link-arg=-Wl,-rpath,{rpath}");
+                println!("cargo:rpath={rpath}");
+            }
+        }
+        if env_vars
+            .ffmpeg_bundle_libs
+            .as_deref()
+            .is_some_and(|x| !x.is_empty() && x != "0")
+        {
+            match target_profile_dir(env_vars) {
+                Some(dest_dir) => bundle(library_names, ffmpeg_lib_dir, &dest_dir),
+                None => println!(
+                    "cargo:warning=rusty_ffmpeg: Cannot find the target directory, FFMPEG_BUNDLE_LIBS is ignored."
+                ),
+            }
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub struct EnvVars {
     docs_rs: Option<String>,
@@ -296,6 +1834,19 @@ pub struct EnvVars {
     ffmpeg_pkg_config_path: Option<PathBuf>,
     ffmpeg_libs_dir: Option<PathBuf>,
     ffmpeg_binding_path: Option<PathBuf>,
+    ffmpeg_binding_cache_dir: Option<PathBuf>,
+    ffmpeg_extra_libs: Option<String>,
+    ffmpeg_lib_suffix: Option<String>,
+    ffmpeg_sysroot: Option<PathBuf>,
+    ffmpeg_source_dir: Option<PathBuf>,
+    ffmpeg_configure_flags: Option<String>,
+    ffmpeg_extra_headers: Option<String>,
+    ffmpeg_bindgen_allowlist: Option<String>,
+    ffmpeg_bindgen_blocklist: Option<String>,
+    ffmpeg_rpath: Option<String>,
+    ffmpeg_bundle_libs: Option<String>,
+    target: Option<String>,
+    host: Option<String>,
 }
 
 impl EnvVars {
@@ -307,7 +1858,27 @@ impl EnvVars {
         println!("cargo:rerun-if-env-changed=FFMPEG_PKG_CONFIG_PATH");
         println!("cargo:rerun-if-env-changed=FFMPEG_LIBS_DIR");
         println!("cargo:rerun-if-env-changed=FFMPEG_BINDING_PATH");
+        println!("cargo:rerun-if-env-changed=FFMPEG_BINDING_CACHE_DIR");
         println!("cargo:rerun-if-env-changed=FFMPEG_LINK_MODE");
+        println!("cargo:rerun-if-env-changed=FFMPEG_EXTRA_LIBS");
+        println!("cargo:rerun-if-env-changed=FFMPEG_LIB_SUFFIX");
+        println!("cargo:rerun-if-env-changed=FFMPEG_SYSROOT");
+        println!("cargo:rerun-if-env-changed=FFMPEG_SOURCE_DIR");
+        println!("cargo:rerun-if-env-changed=FFMPEG_CONFIGURE_FLAGS");
+        println!("cargo:rerun-if-env-changed=FFMPEG_EXTRA_HEADERS");
+        println!("cargo:rerun-if-env-changed=FFMPEG_BINDGEN_ALLOWLIST");
+        println!("cargo:rerun-if-env-changed=FFMPEG_BINDGEN_BLOCKLIST");
+        println!("cargo:rerun-if-env-changed=FFMPEG_RPATH");
+        println!("cargo:rerun-if-env-changed=FFMPEG_BUNDLE_LIBS");
+        println!("cargo:rerun-if-env-changed=BINDGEN_EXTRA_CLANG_ARGS");
+        let target = env::var("TARGET").ok();
+        if let Some(target) = target.as_ref() {
+            println!("cargo:rerun-if-env-changed=BINDGEN_EXTRA_CLANG_ARGS_{target}");
+            println!(
+                "cargo:rerun-if-env-changed=BINDGEN_EXTRA_CLANG_ARGS_{}",
+                target.replace('-', "_")
+            );
+        }
         Self {
             docs_rs: env::var("DOCS_RS").ok(),
             out_dir: env::var("OUT_DIR").ok().map(remove_verbatim),
@@ -316,7 +1887,22 @@ impl EnvVars {
             ffmpeg_pkg_config_path: env::var("FFMPEG_PKG_CONFIG_PATH").ok().map(remove_verbatim),
             ffmpeg_libs_dir: env::var("FFMPEG_LIBS_DIR").ok().map(remove_verbatim),
             ffmpeg_binding_path: env::var("FFMPEG_BINDING_PATH").ok().map(remove_verbatim),
+            ffmpeg_binding_cache_dir: env::var("FFMPEG_BINDING_CACHE_DIR")
+                .ok()
+                .map(remove_verbatim),
             ffmpeg_link_mode: env::var("FFMPEG_LINK_MODE").ok().map(Into::into),
+            ffmpeg_extra_libs: env::var("FFMPEG_EXTRA_LIBS").ok(),
+            ffmpeg_lib_suffix: env::var("FFMPEG_LIB_SUFFIX").ok(),
+            ffmpeg_sysroot: env::var("FFMPEG_SYSROOT").ok().map(remove_verbatim),
+            ffmpeg_source_dir: env::var("FFMPEG_SOURCE_DIR").ok().map(remove_verbatim),
+            ffmpeg_configure_flags: env::var("FFMPEG_CONFIGURE_FLAGS").ok(),
+            ffmpeg_extra_headers: env::var("FFMPEG_EXTRA_HEADERS").ok(),
+            ffmpeg_bindgen_allowlist: env::var("FFMPEG_BINDGEN_ALLOWLIST").ok(),
+            ffmpeg_bindgen_blocklist: env::var("FFMPEG_BINDGEN_BLOCKLIST").ok(),
+            ffmpeg_rpath: env::var("FFMPEG_RPATH").ok(),
+            ffmpeg_bundle_libs: env::var("FFMPEG_BUNDLE_LIBS").ok(),
+            target,
+            host: env::var("HOST").ok(),
         }
     }
 }
@@ -335,13 +1921,64 @@ fn remove_verbatim(path: String) -> PathBuf {
 mod pkg_config_linking {
     use super::*;
 
+    /// Debian style multiarch directory name of a Linux target, e.g.
+    /// `armv7-unknown-linux-gnueabihf` => `arm-linux-gnueabihf`.
+    fn multiarch(target: &str) -> Option<String> {
+        let parts: Vec<&str> = target.split('-').collect();
+        let [arch, _vendor, "linux", abi] = parts[..] else {
+            return None;
+        };
+        let arch = if arch.starts_with("arm") {
+            "arm"
+        } else if matches!(arch, "i586" | "i686") {
+            "i386"
+        } else {
+            arch
+        };
+        Some(format!("{arch}-linux-{abi}"))
+    }
+
+    /// Let pkg-config probe the libraries in the cross sysroot rather than
+    /// the host's. Paths set by the user explicitly are respected.
+    pub fn configure_cross_compilation(env_vars: &EnvVars) {
+        if let Some(ffmpeg_sysroot) = env_vars.ffmpeg_sysroot.as_ref() {
+            if env::var_os("PKG_CONFIG_SYSROOT_DIR").is_none() {
+                env::set_var("PKG_CONFIG_SYSROOT_DIR", ffmpeg_sysroot);
+            }
+            if env::var_os("PKG_CONFIG_LIBDIR").is_none() {
+                let mut dirs = vec![
+                    ffmpeg_sysroot.join("usr/lib/pkgconfig"),
+                    ffmpeg_sysroot.join("usr/share/pkgconfig"),
+                    ffmpeg_sysroot.join("usr/local/lib/pkgconfig"),
+                ];
+                if let Some(multiarch) = env_vars.target.as_deref().and_then(multiarch) {
+                    dirs.push(ffmpeg_sysroot.join(format!("usr/lib/{multiarch}/pkgconfig")));
+                }
+                let dirs = env::join_paths(dirs).expect("Invalid FFMPEG_SYSROOT.");
+                env::set_var("PKG_CONFIG_LIBDIR", dirs);
+            }
+        } else if env_vars.ffmpeg_pkg_config_path.is_some() && env_vars.target != env_vars.host {
+            // The pkg-config files are given explicitly, so they are expected
+            // to describe the FFmpeg built for the target.
+            if env::var_os("PKG_CONFIG_ALLOW_CROSS").is_none() {
+                env::set_var("PKG_CONFIG_ALLOW_CROSS", "1");
+            }
+        }
+    }
+
+    /// Header and library paths of the probed FFmpeg.
+    pub struct ProbedPaths {
+        pub include_paths: Vec<PathBuf>,
+        pub link_paths: Vec<PathBuf>,
+    }
+
     /// Returns error when some library are missing. Otherwise, returns the paths of the libraries.
     ///
     /// Note: no side effect if this function errors.
     pub fn linking_with_pkg_config(
-        library_names: &[&str],
+        library_names: &[String],
         statik: bool,
-    ) -> Result<Vec<PathBuf>, pkg_config::Error> {
+    ) -> Result<ProbedPaths, pkg_config::Error> {
         // dry run for library linking
         for libname in library_names {
             pkg_config::Config::new()
@@ -355,18 +1992,27 @@ mod pkg_config_linking {
 
         // real linking
         let mut paths = HashSet::new();
+        let mut link_paths = Vec::new();
         for libname in library_names {
-            let new_paths = pkg_config::Config::new()
+            let library = pkg_config::Config::new()
                 .statik(statik)
                 .probe(&format!("lib{}", libname))
-                .unwrap_or_else(|_| panic!("{} not found!", libname))
-                .include_paths;
-            for new_path in new_paths {
+                .unwrap_or_else(|_| panic!("{} not found!", libname));
+            for new_path in library.include_paths {
                 let new_path = new_path.to_str().unwrap().to_string();
                 paths.insert(new_path);
             }
+            for link_path in library.link_paths {
+                let link_path = PathBuf::from_path_buf(link_path).unwrap();
+                if !link_paths.contains(&link_path) {
+                    link_paths.push(link_path);
+                }
+            }
         }
-        Ok(paths.into_iter().map(PathBuf::from).collect())
+        Ok(ProbedPaths {
+            include_paths: paths.into_iter().map(PathBuf::from).collect(),
+            link_paths,
+        })
     }
 }
 
@@ -376,27 +2022,39 @@ mod vcpkg_linking {
 
     fn linking_with_vcpkg(
         _env_vars: &EnvVars,
-        _library_names: &[&str],
-    ) -> Result<Vec<PathBuf>, vcpkg::Error> {
-        Ok(vcpkg::Config::new()
-            .find_package("ffmpeg")?
-            .include_paths
-            .into_iter()
-            .map(|x| PathBuf::from_path_buf(x).unwrap())
-            .collect())
+        _library_names: &[String],
+    ) -> Result<vcpkg::Library, vcpkg::Error> {
+        vcpkg::Config::new().find_package("ffmpeg")
     }
 
     pub fn linking_with_vcpkg_and_bindgen(
         env_vars: &EnvVars,
         output_binding_path: &Path,
     ) -> Result<(), vcpkg::Error> {
-        let include_paths = linking_with_vcpkg(env_vars, &*LIBS)?;
+        let library = linking_with_vcpkg(env_vars, &ffmpeg_libs(env_vars))?;
+        let include_paths: Vec<PathBuf> = library
+            .include_paths
+            .into_iter()
+            .map(|x| PathBuf::from_path_buf(x).unwrap())
+            .collect();
+        let link_paths: Vec<PathBuf> = library
+            .link_paths
+            .into_iter()
+            .map(|x| PathBuf::from_path_buf(x).unwrap())
+            .collect();
+        emit_links_metadata(
+            include_paths.first().map(|x| x.as_path()),
+            link_paths.first().map(|x| x.as_path()),
+            if library.is_static {
+                FFmpegLinkMode::Static
+            } else {
+                FFmpegLinkMode::Dynamic
+            },
+        );
         if let Some(ffmpeg_binding_path) = env_vars.ffmpeg_binding_path.as_ref() {
             use_prebuilt_binding(ffmpeg_binding_path, output_binding_path);
         } else {
-            generate_bindings(&include_paths[0], &HEADERS)
-                .write_to_file(output_binding_path)
-                .expect("Cannot write binding to file.");
+            write_bindings(env_vars, &include_paths[0], output_binding_path);
         }
         Ok(())
     }
@@ -405,7 +2063,13 @@ mod vcpkg_linking {
 fn dynamic_linking(env_vars: EnvVars) {
     let ffmpeg_dll_path = env_vars.ffmpeg_dll_path.as_ref().unwrap();
     if ffmpeg_dll_path.is_dir() {
-        linking_with_libs_dir(&*LIBS, ffmpeg_dll_path, FFmpegLinkMode::Dynamic);
+        linking_with_libs_dir(
+            &ffmpeg_libs(&env_vars),
+            ffmpeg_dll_path,
+            FFmpegLinkMode::Dynamic,
+            env_vars.ffmpeg_extra_libs.as_deref(),
+        );
+        runtime_libs::setup(&env_vars, &ffmpeg_libs(&env_vars), ffmpeg_dll_path);
     } else {
         let (lib_name, ffmpeg_dll_dir) = (
             ffmpeg_dll_path
@@ -425,16 +2089,23 @@ fn dynamic_linking(env_vars: EnvVars) {
                 .to_path_buf(),
         );
         linking_with_single_lib(&lib_name, &ffmpeg_dll_dir, FFmpegLinkMode::Dynamic);
+        runtime_libs::setup(&env_vars, &[lib_name], &ffmpeg_dll_dir);
     }
+    emit_links_metadata(
+        env_vars.ffmpeg_include_dir.as_deref(),
+        Some(if ffmpeg_dll_path.is_dir() {
+            ffmpeg_dll_path
+        } else {
+            ffmpeg_dll_path.parent().unwrap()
+        }),
+        FFmpegLinkMode::Dynamic,
+    );
 
     let output_binding_path = &env_vars.out_dir.as_ref().unwrap().join("binding.rs");
     if let Some(ffmpeg_binding_path) = env_vars.ffmpeg_binding_path.as_ref() {
         use_prebuilt_binding(ffmpeg_binding_path, output_binding_path);
     } else if let Some(ffmpeg_include_dir) = env_vars.ffmpeg_include_dir.as_ref() {
-        generate_bindings(ffmpeg_include_dir, &HEADERS)
-            // Is it correct to generate binding to one file? :-/
-            .write_to_file(output_binding_path)
-            .expect("Cannot write binding to file.");
+        write_bindings(&env_vars, ffmpeg_include_dir, output_binding_path);
     } else {
         panic!("No binding generation method is set!");
     }
@@ -449,28 +2120,41 @@ fn linking(env_vars: EnvVars) {
             env_vars: &EnvVars,
             output_binding_path: &Path,
         ) -> Result<(), pkg_config::Error> {
+            let statik = env_vars
+                .ffmpeg_link_mode
+                .map(|x| x.is_static())
+                .unwrap_or_default();
             // Probe libraries(enable emitting cargo metadata)
-            let include_paths = pkg_config_linking::linking_with_pkg_config(
-                &*LIBS,
+            let pkg_config_linking::ProbedPaths {
+                include_paths,
+                link_paths,
+            } = pkg_config_linking::linking_with_pkg_config(&ffmpeg_libs(env_vars), statik)?;
+            if let (false, Some(link_path)) = (statik, link_paths.first()) {
+                runtime_libs::setup(env_vars, &ffmpeg_libs(env_vars), link_path);
+            }
+            emit_links_metadata(
                 env_vars
-                    .ffmpeg_link_mode
-                    .map(|x| x.is_static())
-                    .unwrap_or_default(),
-            )?;
+                    .ffmpeg_include_dir
+                    .as_deref()
+                    .or(include_paths.first().map(|x| x.as_path())),
+                link_paths.first().map(|x| x.as_path()),
+                if statik {
+                    FFmpegLinkMode::Static
+                } else {
+                    FFmpegLinkMode::Dynamic
+                },
+            );
             if let Some(ffmpeg_binding_path) = env_vars.ffmpeg_binding_path.as_ref() {
                 use_prebuilt_binding(ffmpeg_binding_path, output_binding_path);
             } else if let Some(ffmpeg_include_dir) = env_vars.ffmpeg_include_dir.as_ref() {
                 // If use ffmpeg_pkg_config_path with ffmpeg_include_dir, prefer using the user given dir rather than pkg_config_path.
-                generate_bindings(ffmpeg_include_dir, &HEADERS)
-                    .write_to_file(output_binding_path)
-                    .expect("Cannot write binding to file.");
+                write_bindings(env_vars, ffmpeg_include_dir, output_binding_path);
             } else {
-                generate_bindings(&include_paths[0], &HEADERS)
-                    .write_to_file(output_binding_path)
-                    .expect("Cannot write binding to file.");
+                write_bindings(env_vars, &include_paths[0], output_binding_path);
             }
             Ok(())
         }
+        pkg_config_linking::configure_cross_compilation(&env_vars);
         // Hint: set PKG_CONFIG_PATH to some placeholder value will let pkg_config probing system library.
         if let Some(ffmpeg_pkg_config_path) = env_vars.ffmpeg_pkg_config_path.as_ref() {
             if !Path::new(ffmpeg_pkg_config_path).exists() {
@@ -483,17 +2167,25 @@ fn linking(env_vars: EnvVars) {
             linking_with_pkg_config_and_bindgen(&env_vars, output_binding_path)
                 .expect("Static linking with pkg-config failed.");
         } else if let Some(ffmpeg_libs_dir) = env_vars.ffmpeg_libs_dir.as_ref() {
+            let mode = env_vars.ffmpeg_link_mode.unwrap_or(FFmpegLinkMode::Static);
             linking_with_libs_dir(
-                &*LIBS,
+                &ffmpeg_libs(&env_vars),
                 ffmpeg_libs_dir,
-                env_vars.ffmpeg_link_mode.unwrap_or(FFmpegLinkMode::Static),
+                mode,
+                env_vars.ffmpeg_extra_libs.as_deref(),
+            );
+            if mode == FFmpegLinkMode::Dynamic {
+                runtime_libs::setup(&env_vars, &ffmpeg_libs(&env_vars), ffmpeg_libs_dir);
+            }
+            emit_links_metadata(
+                env_vars.ffmpeg_include_dir.as_deref(),
+                Some(ffmpeg_libs_dir),
+                mode,
             );
             if let Some(ffmpeg_binding_path) = env_vars.ffmpeg_binding_path.as_ref() {
                 use_prebuilt_binding(ffmpeg_binding_path, output_binding_path);
             } else if let Some(ffmpeg_include_dir) = env_vars.ffmpeg_include_dir.as_ref() {
-                generate_bindings(ffmpeg_include_dir, &HEADERS)
-                    .write_to_file(output_binding_path)
-                    .expect("Cannot write binding to file.");
+                write_bindings(&env_vars, ffmpeg_include_dir, output_binding_path);
             } else {
                 panic!("No binding generation method is set!");
             }
@@ -545,17 +2237,25 @@ Enable `link_vcpkg_ffmpeg` feature if you want to link ffmpeg libraries installe
     #[cfg(target_os = "windows")]
     {
         if let Some(ffmpeg_libs_dir) = env_vars.ffmpeg_libs_dir.as_ref() {
+            let mode = env_vars.ffmpeg_link_mode.unwrap_or(FFmpegLinkMode::Static);
             linking_with_libs_dir(
-                &*LIBS,
+                &ffmpeg_libs(&env_vars),
                 ffmpeg_libs_dir,
-                env_vars.ffmpeg_link_mode.unwrap_or(FFmpegLinkMode::Static),
+                mode,
+                env_vars.ffmpeg_extra_libs.as_deref(),
+            );
+            if mode == FFmpegLinkMode::Dynamic {
+                runtime_libs::setup(&env_vars, &ffmpeg_libs(&env_vars), ffmpeg_libs_dir);
+            }
+            emit_links_metadata(
+                env_vars.ffmpeg_include_dir.as_deref(),
+                Some(ffmpeg_libs_dir),
+                mode,
             );
             if let Some(ffmpeg_binding_path) = env_vars.ffmpeg_binding_path.as_ref() {
                 use_prebuilt_binding(ffmpeg_binding_path, output_binding_path);
             } else if let Some(ffmpeg_include_dir) = env_vars.ffmpeg_include_dir.as_ref() {
-                generate_bindings(ffmpeg_include_dir, &HEADERS)
-                    .write_to_file(output_binding_path)
-                    .expect("Cannot write binding to file.");
+                write_bindings(&env_vars, ffmpeg_include_dir, output_binding_path);
             } else {
                 panic!("No binding generation method is set!");
             }
@@ -575,6 +2275,197 @@ Enable `link_vcpkg_ffmpeg` feature if you want to link ffmpeg provided by vcpkg.
     }
 }
 
+/// Build FFmpeg from `FFMPEG_SOURCE_DIR` and link it statically.
+#[cfg(feature = "build_from_source")]
+mod source_build {
+    use super::*;
+    use std::process::Command;
+
+    /// Flags passed before `FFMPEG_CONFIGURE_FLAGS`, which can override them.
+    const DEFAULT_CONFIGURE_FLAGS: [&str; 5] = [
+        "--disable-programs",
+        "--disable-doc",
+        "--enable-static",
+        "--disable-shared",
+        "--enable-pic",
+    ];
+
+    /// FFmpeg `--target-os` of a Rust target triple.
+    fn target_os(target: &str) -> Option<&'static str> {
+        if target.contains("android") {
+            Some("android")
+        } else if target.contains("linux") {
+            Some("linux")
+        } else if target.contains("apple") {
+            Some("darwin")
+        } else if target.contains("windows-gnu") {
+            Some("mingw32")
+        } else if target.contains("freebsd") {
+            Some("freebsd")
+        } else {
+            None
+        }
+    }
+
+    /// FFmpeg `--arch` of a Rust target triple, e.g. `armv7` is `arm` and
+    /// `i686` is `x86`.
+    fn target_arch(target: &str) -> Option<&'static str> {
+        let arch = target.split('-').next().unwrap();
+        if arch == "x86_64" {
+            Some("x86_64")
+        } else if arch.starts_with('i') && arch.ends_with("86") {
+            Some("x86")
+        } else if arch.starts_with("aarch64") {
+            Some("aarch64")
+        } else if arch.starts_with("arm") || arch.starts_with("thumb") {
+            Some("arm")
+        } else if arch.starts_with("riscv64") {
+            Some("riscv64")
+        } else if arch.starts_with("powerpc64") {
+            Some("ppc64")
+        } else if arch == "powerpc" {
+            Some("ppc")
+        } else if arch.starts_with("mips64") {
+            Some("mips64")
+        } else if arch.starts_with("mips") {
+            Some("mips")
+        } else if arch == "loongarch64" {
+            Some("loongarch64")
+        } else if arch == "s390x" {
+            Some("s390")
+        } else {
+            None
+        }
+    }
+
+    /// The build runs FFmpeg's `configure` script and `make`, which need a
+    /// Unix host, and links the `lib*.a` archives, which MSVC builds of
+    /// FFmpeg don't produce.
+    fn check_supported(env_vars: &EnvVars) {
+        let host = env_vars.host.as_deref().unwrap_or_default();
+        let target = env_vars.target.as_deref().unwrap_or_default();
+        if host.contains("windows") {
+            panic!(
+                "`build_from_source` feature doesn't support Windows hosts, build FFmpeg in MSYS2 \
+                and set FFMPEG_LIBS_DIR and FFMPEG_INCLUDE_DIR instead."
+            );
+        }
+        if target.contains("msvc") {
+            panic!(
+                "`build_from_source` feature doesn't support MSVC targets, build FFmpeg with \
+                `--toolchain=msvc` and set FFMPEG_LIBS_DIR and FFMPEG_INCLUDE_DIR instead."
+            );
+        }
+    }
+
+    fn configure_flags(env_vars: &EnvVars, prefix: &Path) -> Vec<String> {
+        let mut flags = vec![format!("--prefix={prefix}")];
+        flags.extend(DEFAULT_CONFIGURE_FLAGS.iter().map(|x| x.to_string()));
+        if let (Some(target), Some(host)) = (env_vars.target.as_ref(), env_vars.host.as_ref()) {
+            if target != host {
+                flags.push("--enable-cross-compile".to_string());
+                let user_arch = env_vars
+                    .ffmpeg_configure_flags
+                    .as_ref()
+                    .is_some_and(|x| x.contains("--arch="));
+                if let Some(arch) = target_arch(target) {
+                    flags.push(format!("--arch={arch}"));
+                } else if !user_arch {
+                    panic!(
+                        "`build_from_source` feature: unknown FFmpeg arch of `{target}`, \
+                        pass `--arch` in FFMPEG_CONFIGURE_FLAGS."
+                    );
+                }
+                if let Some(target_os) = target_os(target) {
+                    flags.push(format!("--target-os={target_os}"));
+                }
+            }
+        }
+        if let Some(ffmpeg_sysroot) = env_vars.ffmpeg_sysroot.as_ref() {
+            flags.push(format!("--sysroot={ffmpeg_sysroot}"));
+        }
+        if let Some(configure_flags) = env_vars.ffmpeg_configure_flags.as_ref() {
+            flags.extend(configure_flags.split_whitespace().map(str::to_string));
+        }
+        flags
+    }
+
+    fn run(command: &mut Command) {
+        let status = command
+            .status()
+            .unwrap_or_else(|e| panic!("Failed to run {command:?}: {e}"));
+        if !status.success() {
+            panic!("{command:?} failed with {status}.");
+        }
+    }
+
+    /// Configure, build and install FFmpeg into `OUT_DIR/ffmpeg`, which is
+    /// skipped if it has been installed from the same source with the same
+    /// flags. Returns the install prefix.
+    fn build(env_vars: &EnvVars) -> PathBuf {
+        let source_dir = env_vars
+            .ffmpeg_source_dir
+            .as_ref()
+            .expect("`build_from_source` feature is enabled, but FFMPEG_SOURCE_DIR is not set.");
+        let configure = source_dir.join("configure");
+        if !configure.exists() {
+            panic!("FFMPEG_SOURCE_DIR: `{configure}` doesn't exist.");
+        }
+        let out_dir = env_vars.out_dir.as_ref().unwrap();
+        let prefix = out_dir.join("ffmpeg");
+        let build_dir = out_dir.join("ffmpeg-build");
+        let flags = configure_flags(env_vars, &prefix);
+
+        let stamp_path = prefix.join("rusty_ffmpeg.stamp");
+        let stamp = format!("{source_dir}\n{}", flags.join("\n"));
+        if fs::read_to_string(&stamp_path).is_ok_and(|x| x == stamp) {
+            return prefix;
+        }
+
+        fs::create_dir_all(&build_dir).expect("Cannot create FFmpeg build directory.");
+        run(Command::new(&configure)
+            .args(&flags)
+            .current_dir(&build_dir));
+        let mut make = Command::new("make");
+        if let Ok(jobs) = env::var("NUM_JOBS") {
+            make.arg(format!("-j{jobs}"));
+        }
+        run(make.current_dir(&build_dir));
+        run(Command::new("make").arg("install").current_dir(&build_dir));
+        fs::write(&stamp_path, stamp).expect("Cannot write FFmpeg build stamp.");
+        prefix
+    }
+
+    pub fn linking(env_vars: EnvVars) {
+        check_supported(&env_vars);
+        let prefix = build(&env_vars);
+        let (ffmpeg_libs_dir, ffmpeg_include_dir) = (prefix.join("lib"), prefix.join("include"));
+        // Libraries disabled by the configure flags are not installed.
+        let library_names: Vec<String> = ffmpeg_libs(&env_vars)
+            .into_iter()
+            .filter(|name| ffmpeg_libs_dir.join(format!("lib{name}.a")).exists())
+            .collect();
+        linking_with_libs_dir(
+            &library_names,
+            &ffmpeg_libs_dir,
+            FFmpegLinkMode::Static,
+            env_vars.ffmpeg_extra_libs.as_deref(),
+        );
+        emit_links_metadata(
+            Some(&ffmpeg_include_dir),
+            Some(&ffmpeg_libs_dir),
+            FFmpegLinkMode::Static,
+        );
+
+        let output_binding_path = &env_vars.out_dir.as_ref().unwrap().join("binding.rs");
+        if let Some(ffmpeg_binding_path) = env_vars.ffmpeg_binding_path.as_ref() {
+            use_prebuilt_binding(ffmpeg_binding_path, output_binding_path);
+        } else {
+            write_bindings(&env_vars, &ffmpeg_include_dir, output_binding_path);
+        }
+    }
+}
+
 fn docs_rs_linking(env_vars: EnvVars) {
     // If it's a documentation generation from docs.rs, just copy the bindings
     // generated locally to `OUT_DIR`. We do this because the building
@@ -585,6 +2476,9 @@ fn docs_rs_linking(env_vars: EnvVars) {
     // `OUT_DIR`.
     let binding_file_path = &env_vars.out_dir.as_ref().unwrap().join("binding.rs");
     use_prebuilt_binding(Path::new("src/binding.rs"), binding_file_path);
+    // It's the binding of the latest supported FFmpeg whatever the version
+    // features are, `crate::compat` follows it.
+    println!("cargo:rustc-cfg=rusty_ffmpeg_prebuilt_binding");
 }
 
 /// When use_prebuilt_binding feature is enabled, use the prebuilt binding
@@ -594,12 +2488,22 @@ fn docs_rs_linking(env_vars: EnvVars) {
 fn use_prebuilt_binding_feature(env_vars: &EnvVars) {
     let binding_file_path = &env_vars.out_dir.as_ref().unwrap().join("binding.rs");
     use_prebuilt_binding(Path::new("src/binding.rs"), binding_file_path);
+    // It's the binding of the latest supported FFmpeg whatever the version
+    // features are, `crate::compat` follows it. Bindings from
+    // `FFMPEG_BINDING_PATH` may be of any version, so `crate::compat` follows
+    // the version features for them instead.
+    println!("cargo:rustc-cfg=rusty_ffmpeg_prebuilt_binding");
 }
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(rusty_ffmpeg_header_macros)");
+    println!("cargo:rustc-check-cfg=cfg(rusty_ffmpeg_prebuilt_binding)");
     let env_vars = EnvVars::init();
     if env_vars.docs_rs.is_some() {
         docs_rs_linking(env_vars);
+    } else if cfg!(feature = "build_from_source") {
+        #[cfg(feature = "build_from_source")]
+        source_build::linking(env_vars);
     } else if env_vars.ffmpeg_dll_path.is_some() {
         dynamic_linking(env_vars);
     } else {
@@ -611,4 +2515,7 @@ fn main() {
     // with our prebuilt one to ensure consistent FFmpeg 7 API signatures
     #[cfg(feature = "use_prebuilt_binding")]
     use_prebuilt_binding_feature(&EnvVars::init());
+
+    #[cfg(feature = "verify_headers")]
+    header_probe::generate(&EnvVars::init());
 }
@@ -1,6 +1,9 @@
 //! Port from Original code: https://github.com/leandromoreira/ffmpeg-libav-tutorial/blob/master/0_hello_world.c
 
-use rusty_ffmpeg::ffi;
+use rusty_ffmpeg::{
+    compat::{self, ChannelLayoutExt},
+    ffi,
+};
 
 use std::{
     ffi::{CStr, CString},
@@ -100,7 +103,8 @@ fn main() {
             ffi::AVMEDIA_TYPE_AUDIO => {
                 println!(
                     "Audio Codec: {} channels, sample rate {}",
-                    local_codec_params.ch_layout.nb_channels, local_codec_params.sample_rate
+                    local_codec_params.nb_channels(),
+                    local_codec_params.sample_rate
                 );
             }
             _ => {}
@@ -177,7 +181,7 @@ fn decode_packet(
         } else {
             println!(
                 "Frame {} (type={}, size={} bytes) pts {}",
-                codec_context.frame_num,
+                compat::frame_num(codec_context),
                 unsafe { ffi::av_get_picture_type_char(frame.pict_type) },
                 frame.linesize[0] * frame.height,
                 frame.pts,
@@ -185,7 +189,7 @@ fn decode_packet(
 
             let frame_filename = format!(
                 "./examples/slice/output/frame-{}.pgm",
-                codec_context.frame_num
+                compat::frame_num(codec_context)
             );
             let width = frame.width as usize;
             let height = frame.height as usize;
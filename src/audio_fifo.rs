@@ -0,0 +1,272 @@
+//! Re-chunk audio into frames of a fixed number of samples, e.g. the
+//! `frame_size` of an encoder(`AVAudioFifo`).
+use crate::compat::ChannelLayoutExt;
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use crate::frame::Frame;
+use crate::sample_format::{check_audio_frame, SampleFormat};
+use std::ffi::c_int;
+use std::ptr::NonNull;
+
+/// A FIFO of audio samples, written with frames of any length and read as
+/// frames of `frame_size` samples.
+///
+/// The pts of the written frames is in the time base passed to
+/// [`AudioFifo::new`], the pts of the read frames is in `1/sample_rate` and
+/// counted in samples from the pts of the frame written into the empty FIFO,
+/// it's `AV_NOPTS_VALUE` if that frame has no pts.
+pub struct AudioFifo {
+    fifo: NonNull<ffi::AVAudioFifo>,
+    format: SampleFormat,
+    channels: c_int,
+    frame_size: usize,
+    /// Channel layout and sample rate of the written frames.
+    channel_mask: u64,
+    sample_rate: c_int,
+    /// Time base of the pts of the written frames.
+    time_base: ffi::AVRational,
+    /// pts of the first sample in the FIFO, in `1/sample_rate`.
+    pts: i64,
+}
+
+unsafe impl Send for AudioFifo {}
+
+impl AudioFifo {
+    /// Allocate a FIFO(`av_audio_fifo_alloc`) of audio in `format` with
+    /// `channels` channels, read as frames of `frame_size` samples. The pts
+    /// of the written frames is in `time_base`.
+    pub fn new(
+        format: SampleFormat,
+        channels: usize,
+        frame_size: usize,
+        time_base: ffi::AVRational,
+    ) -> Result<Self> {
+        let channels = c_int::try_from(channels).map_err(|_| Error::INVALID_ARGUMENT)?;
+        let frame_size_c = c_int::try_from(frame_size).map_err(|_| Error::INVALID_ARGUMENT)?;
+        if channels <= 0 || frame_size == 0 || time_base.num <= 0 || time_base.den <= 0 {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        let fifo = unsafe { ffi::av_audio_fifo_alloc(format.as_raw(), channels, frame_size_c) };
+        Ok(Self {
+            fifo: NonNull::new(fifo).ok_or(Error::OUT_OF_MEMORY)?,
+            format,
+            channels,
+            frame_size,
+            channel_mask: 0,
+            sample_rate: 0,
+            time_base,
+            pts: ffi::AV_NOPTS_VALUE,
+        })
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Number of samples per channel in the FIFO(`av_audio_fifo_size`).
+    pub fn size(&self) -> usize {
+        unsafe { ffi::av_audio_fifo_size(self.fifo.as_ptr()) }.max(0) as usize
+    }
+
+    /// Make room for `additional` more samples(`av_audio_fifo_realloc`),
+    /// which [`AudioFifo::write`] does when needed.
+    pub fn reserve(&mut self, additional: usize) -> Result<()> {
+        let size =
+            c_int::try_from(self.size() + additional).map_err(|_| Error::INVALID_ARGUMENT)?;
+        check(unsafe { ffi::av_audio_fifo_realloc(self.fifo.as_ptr(), size) })?;
+        Ok(())
+    }
+
+    /// Append the samples of a frame, which must have the format and the
+    /// number of channels of the FIFO, the sample rate of the previously
+    /// written frames, and a sample rate if it has a pts.
+    pub fn write(&mut self, frame: &Frame) -> Result<()> {
+        if SampleFormat::from_c_int(frame.format) != self.format
+            || frame.nb_channels() != self.channels
+            || (self.sample_rate != 0 && frame.sample_rate != self.sample_rate)
+            || (frame.pts != ffi::AV_NOPTS_VALUE && frame.sample_rate <= 0)
+        {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        if frame.nb_samples == 0 {
+            return Ok(());
+        }
+        check_audio_frame(frame)?;
+        if self.size() == 0 {
+            self.pts = if frame.pts == ffi::AV_NOPTS_VALUE {
+                frame.pts
+            } else {
+                let sample_time_base = ffi::AVRational {
+                    num: 1,
+                    den: frame.sample_rate,
+                };
+                unsafe { ffi::av_rescale_q(frame.pts, self.time_base, sample_time_base) }
+            };
+        }
+        self.channel_mask = frame.channel_mask();
+        self.sample_rate = frame.sample_rate;
+        let written = check(unsafe {
+            ffi::av_audio_fifo_write(
+                self.fifo.as_ptr(),
+                frame.extended_data as _,
+                frame.nb_samples,
+            )
+        })?;
+        if written < frame.nb_samples {
+            return Err(Error::OUT_OF_MEMORY);
+        }
+        Ok(())
+    }
+
+    fn alloc_frame(&self, samples: usize) -> Result<Frame> {
+        let mut frame = Frame::new()?;
+        // The buffers are allocated from these fields.
+        let raw = unsafe { frame.as_mut_raw() };
+        raw.format = self.format.to_c_int();
+        raw.nb_samples = samples as c_int;
+        raw.sample_rate = self.sample_rate;
+        raw.pts = self.pts;
+        if self.channel_mask.count_ones() as c_int == self.channels {
+            raw.set_channel_mask(self.channel_mask)?;
+        } else {
+            raw.set_default_channel_layout(self.channels)?;
+        }
+        check(unsafe { ffi::av_frame_get_buffer(frame.as_mut_ptr(), 0) })?;
+        Ok(frame)
+    }
+
+    fn advance(&mut self, samples: usize) {
+        if self.pts != ffi::AV_NOPTS_VALUE {
+            self.pts += samples as i64;
+        }
+    }
+
+    fn read_samples(&mut self, samples: usize) -> Result<Frame> {
+        let frame = self.alloc_frame(samples)?;
+        check(unsafe {
+            ffi::av_audio_fifo_read(
+                self.fifo.as_ptr(),
+                frame.extended_data as _,
+                samples as c_int,
+            )
+        })?;
+        self.advance(samples);
+        Ok(frame)
+    }
+
+    /// Read a frame of `frame_size` samples, `None` if there are not enough
+    /// samples.
+    pub fn read(&mut self) -> Result<Option<Frame>> {
+        if self.size() < self.frame_size {
+            return Ok(None);
+        }
+        self.read_samples(self.frame_size).map(Some)
+    }
+
+    /// Read at EOF: a frame of `frame_size` samples, or the final partial
+    /// frame of the remaining samples, `None` if the FIFO is empty.
+    pub fn flush(&mut self) -> Result<Option<Frame>> {
+        match self.size().min(self.frame_size) {
+            0 => Ok(None),
+            samples => self.read_samples(samples).map(Some),
+        }
+    }
+
+    /// Copy up to `samples` samples without removing them
+    /// (`av_audio_fifo_peek`), `None` if the FIFO is empty.
+    pub fn peek(&self, samples: usize) -> Result<Option<Frame>> {
+        let samples = samples.min(self.size());
+        if samples == 0 {
+            return Ok(None);
+        }
+        let frame = self.alloc_frame(samples)?;
+        check(unsafe {
+            ffi::av_audio_fifo_peek(
+                self.fifo.as_ptr(),
+                frame.extended_data as _,
+                samples as c_int,
+            )
+        })?;
+        Ok(Some(frame))
+    }
+
+    /// Discard up to `samples` samples(`av_audio_fifo_drain`).
+    pub fn drain(&mut self, samples: usize) -> Result<()> {
+        let samples = samples.min(self.size());
+        check(unsafe { ffi::av_audio_fifo_drain(self.fifo.as_ptr(), samples as c_int) })?;
+        self.advance(samples);
+        Ok(())
+    }
+
+    /// Discard all the samples(`av_audio_fifo_reset`).
+    pub fn clear(&mut self) {
+        unsafe { ffi::av_audio_fifo_reset(self.fifo.as_ptr()) };
+        self.sample_rate = 0;
+        self.pts = ffi::AV_NOPTS_VALUE;
+    }
+}
+
+impl Drop for AudioFifo {
+    fn drop(&mut self) {
+        unsafe { ffi::av_audio_fifo_free(self.fifo.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sample_format::SampleBuffer;
+
+    fn s16_frame(start: i16, samples: usize) -> Frame {
+        let mut buffer = SampleBuffer::new(ffi::AV_SAMPLE_FMT_S16.into(), 1, samples).unwrap();
+        for (i, sample) in buffer.plane_mut::<i16>(0).unwrap().iter_mut().enumerate() {
+            *sample = start + i as i16;
+        }
+        let mut frame = Frame::new().unwrap();
+        buffer.to_frame(&mut frame).unwrap();
+        frame.set_sample_rate(7000);
+        frame
+    }
+
+    #[test]
+    fn test_rechunk() {
+        // pts in milliseconds, 700 samples at 7000 Hz are 100 ms.
+        let time_base = ffi::AVRational { num: 1, den: 1000 };
+        let mut fifo = AudioFifo::new(ffi::AV_SAMPLE_FMT_S16.into(), 1, 1024, time_base).unwrap();
+        for i in 0..3 {
+            let mut frame = s16_frame(i * 700, 700);
+            frame.set_pts(10 + i64::from(i) * 100);
+            fifo.write(&frame).unwrap();
+        }
+        assert_eq!(fifo.size(), 2100);
+        let peeked = fifo.peek(10).unwrap().unwrap();
+        assert_eq!(peeked.nb_samples, 10);
+        assert_eq!(peeked.pts, 70);
+
+        let mut frames = Vec::new();
+        while let Some(frame) = fifo.read().unwrap() {
+            frames.push(frame);
+        }
+        assert_eq!(fifo.size(), 52);
+        while let Some(frame) = fifo.flush().unwrap() {
+            frames.push(frame);
+        }
+        let sizes: Vec<_> = frames.iter().map(|x| (x.nb_samples, x.pts)).collect();
+        assert_eq!(sizes, [(1024, 70), (1024, 1094), (52, 2118)]);
+        let last = SampleBuffer::from_frame(&frames[2]).unwrap();
+        assert_eq!(last.plane::<i16>(0).unwrap()[0], 2048);
+
+        assert!(fifo.write(&s16_frame(0, 10)).is_ok());
+        fifo.drain(4).unwrap();
+        assert_eq!(fifo.size(), 6);
+        let wrong_format = SampleBuffer::new(ffi::AV_SAMPLE_FMT_FLT.into(), 1, 10).unwrap();
+        let mut frame = Frame::new().unwrap();
+        wrong_format.to_frame(&mut frame).unwrap();
+        assert!(fifo.write(&frame).is_err());
+
+        // More samples than the buffers hold.
+        let mut frame = s16_frame(0, 10);
+        unsafe { frame.as_mut_raw().nb_samples = 1 << 20 };
+        assert!(fifo.write(&frame).is_err());
+    }
+}
@@ -2,9 +2,10 @@ pub mod _avutil;
 pub mod common;
 #[rustfmt::skip]
 pub mod error;
+#[cfg(not(rusty_ffmpeg_header_macros))]
 #[rustfmt::skip]
 pub mod pixfmt;
 pub mod rational;
-#[cfg(feature = "ffmpeg6")]
+#[cfg(all(feature = "ffmpeg6", not(rusty_ffmpeg_header_macros)))]
 #[rustfmt::skip]
 pub mod channel_layout;
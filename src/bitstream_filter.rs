@@ -0,0 +1,169 @@
+//! Bitstream filters and filter chains(`av_bsf_*`), e.g. `h264_mp4toannexb`
+//! converting H.264 in MP4 to Annex B.
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use crate::packet::Packet;
+use std::ffi::CString;
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+/// Result of [`BitstreamFilter::receive`].
+#[derive(Debug)]
+pub enum Received {
+    Packet(Packet),
+    /// More input is needed(`EAGAIN`).
+    NeedsInput,
+    /// All the output has been received after
+    /// [`BitstreamFilter::send_eof`](`EOF`).
+    Eof,
+}
+
+/// An initialized bitstream filter or chain of filters(`AVBSFContext`).
+pub struct BitstreamFilter(NonNull<ffi::AVBSFContext>);
+
+unsafe impl Send for BitstreamFilter {}
+
+impl BitstreamFilter {
+    /// Create a filter chain from a description parsed by
+    /// `av_bsf_list_parse_str`, e.g. `"h264_mp4toannexb"` or
+    /// `"h264_mp4toannexb,dump_extra=freq=keyframe"`, for the input stream of
+    /// the parameters and the time base.
+    pub fn new(
+        filters: &str,
+        par_in: &ffi::AVCodecParameters,
+        time_base_in: ffi::AVRational,
+    ) -> Result<Self> {
+        let filters = CString::new(filters).map_err(|_| Error::INVALID_ARGUMENT)?;
+        let mut ctx = ptr::null_mut();
+        check(unsafe { ffi::av_bsf_list_parse_str(filters.as_ptr(), &mut ctx) })?;
+        let mut bsf = Self(NonNull::new(ctx).ok_or(Error::OUT_OF_MEMORY)?);
+        unsafe {
+            let ctx = bsf.0.as_mut();
+            check(ffi::avcodec_parameters_copy(ctx.par_in, par_in))?;
+            ctx.time_base_in = time_base_in;
+            check(ffi::av_bsf_init(ctx))?;
+        }
+        Ok(bsf)
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVBSFContext {
+        self.0.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVBSFContext {
+        self.0.as_ptr()
+    }
+
+    /// Parameters of the output stream, e.g. with the extradata rewritten.
+    pub fn par_out(&self) -> &ffi::AVCodecParameters {
+        unsafe { &*self.0.as_ref().par_out }
+    }
+
+    /// Time base of the output packets.
+    pub fn time_base_out(&self) -> ffi::AVRational {
+        unsafe { self.0.as_ref().time_base_out }
+    }
+
+    /// Submit a packet(`av_bsf_send_packet`), which is taken and left blank
+    /// on success. Fails with [`Error::AGAIN`] if the output must be
+    /// received first.
+    pub fn send(&mut self, packet: &mut Packet) -> Result<()> {
+        check(unsafe { ffi::av_bsf_send_packet(self.as_mut_ptr(), packet.as_mut_ptr()) })?;
+        Ok(())
+    }
+
+    /// Signal the end of the input, after which the remaining output can be
+    /// received.
+    pub fn send_eof(&mut self) -> Result<()> {
+        check(unsafe { ffi::av_bsf_send_packet(self.as_mut_ptr(), ptr::null_mut()) })?;
+        Ok(())
+    }
+
+    /// Retrieve a filtered packet(`av_bsf_receive_packet`).
+    pub fn receive(&mut self) -> Result<Received> {
+        let mut packet = Packet::new()?;
+        let ret = unsafe { ffi::av_bsf_receive_packet(self.as_mut_ptr(), packet.as_mut_ptr()) };
+        match check(ret) {
+            Ok(_) => Ok(Received::Packet(packet)),
+            Err(Error::AGAIN) => Ok(Received::NeedsInput),
+            Err(Error::EOF) => Ok(Received::Eof),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Receive packets until more input is needed or the end of the output.
+    fn receive_all(&mut self) -> Result<Vec<Packet>> {
+        let mut packets = Vec::new();
+        while let Received::Packet(packet) = self.receive()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    /// Filter a packet, returning the output packets available, possibly
+    /// none.
+    pub fn filter(&mut self, mut packet: Packet) -> Result<Vec<Packet>> {
+        self.send(&mut packet)?;
+        self.receive_all()
+    }
+
+    /// Signal the end of the input and return the remaining output packets.
+    /// Call [`BitstreamFilter::flush`] before reusing the filter.
+    pub fn finish(&mut self) -> Result<Vec<Packet>> {
+        self.send_eof()?;
+        self.receive_all()
+    }
+
+    /// Reset the state and discard the buffered packets(`av_bsf_flush`),
+    /// e.g. after seeking.
+    pub fn flush(&mut self) {
+        unsafe { ffi::av_bsf_flush(self.as_mut_ptr()) };
+    }
+}
+
+impl Drop for BitstreamFilter {
+    fn drop(&mut self) {
+        let mut ctx = self.0.as_ptr();
+        unsafe { ffi::av_bsf_free(&mut ctx) };
+    }
+}
+
+impl fmt::Debug for BitstreamFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitstreamFilter")
+            .field("time_base_out", &self.time_base_out())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_null_chain() {
+        let mut par = unsafe { ffi::avcodec_parameters_alloc() };
+        unsafe { (*par).codec_type = ffi::AVMEDIA_TYPE_VIDEO };
+        let time_base = ffi::AVRational { num: 1, den: 90000 };
+        let par_in = unsafe { &*par };
+        assert!(BitstreamFilter::new("no_such_filter", par_in, time_base).is_err());
+
+        let mut bsf = BitstreamFilter::new("null,null", par_in, time_base).unwrap();
+        assert_eq!(bsf.time_base_out().den, 90000);
+        let mut packet = Packet::from_slice(&[0, 0, 0, 1, 9]).unwrap();
+        packet.set_pts(3000);
+        let output = bsf.filter(packet).unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].data(), &[0, 0, 0, 1, 9]);
+        assert_eq!(output[0].pts, 3000);
+        assert!(matches!(bsf.receive().unwrap(), Received::NeedsInput));
+        assert!(bsf.finish().unwrap().is_empty());
+        assert!(matches!(bsf.receive().unwrap(), Received::Eof));
+        bsf.flush();
+        assert_eq!(
+            bsf.filter(Packet::from_slice(&[1]).unwrap()).unwrap().len(),
+            1
+        );
+        unsafe { ffi::avcodec_parameters_free(&mut par) };
+    }
+}
@@ -0,0 +1,302 @@
+//! Accessors behaving identically on every supported FFmpeg version, so
+//! downstream crates don't need to `#[cfg]` on the version features for the
+//! fields and functions changed across FFmpeg 5 to 8.
+use crate::error::{Error, Result};
+use crate::ffi;
+use std::ffi::c_int;
+
+/// Number of frames returned from the decoder or passed to the encoder,
+/// `AVCodecContext.frame_num`(FFmpeg 6+) or `frame_number`.
+pub fn frame_num(codec_ctx: &ffi::AVCodecContext) -> i64 {
+    #[cfg(any(feature = "ffmpeg6", rusty_ffmpeg_prebuilt_binding))]
+    return codec_ctx.frame_num;
+    #[cfg(not(any(feature = "ffmpeg6", rusty_ffmpeg_prebuilt_binding)))]
+    return codec_ctx.frame_number.into();
+}
+
+/// Properties of `AVFrame` which were moved into `AVFrame.flags` in FFmpeg
+/// 6.1(`AV_FRAME_FLAG_KEY`, `AV_FRAME_FLAG_INTERLACED` and
+/// `AV_FRAME_FLAG_TOP_FIELD_FIRST`).
+pub trait FrameExt {
+    fn is_key_frame(&self) -> bool;
+    fn set_key_frame(&mut self, key_frame: bool);
+    fn is_interlaced(&self) -> bool;
+    fn set_interlaced(&mut self, interlaced: bool);
+    fn is_top_field_first(&self) -> bool;
+    fn set_top_field_first(&mut self, top_field_first: bool);
+}
+
+#[cfg(any(feature = "ffmpeg6_1", rusty_ffmpeg_prebuilt_binding))]
+fn frame_flag(frame: &ffi::AVFrame, flag: u32) -> bool {
+    frame.flags & flag as c_int != 0
+}
+
+#[cfg(any(feature = "ffmpeg6_1", rusty_ffmpeg_prebuilt_binding))]
+fn set_frame_flag(frame: &mut ffi::AVFrame, flag: u32, value: bool) {
+    if value {
+        frame.flags |= flag as c_int;
+    } else {
+        frame.flags &= !(flag as c_int);
+    }
+}
+
+#[cfg(any(feature = "ffmpeg6_1", rusty_ffmpeg_prebuilt_binding))]
+impl FrameExt for ffi::AVFrame {
+    fn is_key_frame(&self) -> bool {
+        frame_flag(self, ffi::AV_FRAME_FLAG_KEY)
+    }
+
+    fn set_key_frame(&mut self, key_frame: bool) {
+        set_frame_flag(self, ffi::AV_FRAME_FLAG_KEY, key_frame);
+    }
+
+    fn is_interlaced(&self) -> bool {
+        frame_flag(self, ffi::AV_FRAME_FLAG_INTERLACED)
+    }
+
+    fn set_interlaced(&mut self, interlaced: bool) {
+        set_frame_flag(self, ffi::AV_FRAME_FLAG_INTERLACED, interlaced);
+    }
+
+    fn is_top_field_first(&self) -> bool {
+        frame_flag(self, ffi::AV_FRAME_FLAG_TOP_FIELD_FIRST)
+    }
+
+    fn set_top_field_first(&mut self, top_field_first: bool) {
+        set_frame_flag(self, ffi::AV_FRAME_FLAG_TOP_FIELD_FIRST, top_field_first);
+    }
+}
+
+#[cfg(not(any(feature = "ffmpeg6_1", rusty_ffmpeg_prebuilt_binding)))]
+impl FrameExt for ffi::AVFrame {
+    fn is_key_frame(&self) -> bool {
+        self.key_frame != 0
+    }
+
+    fn set_key_frame(&mut self, key_frame: bool) {
+        self.key_frame = key_frame.into();
+    }
+
+    fn is_interlaced(&self) -> bool {
+        self.interlaced_frame != 0
+    }
+
+    fn set_interlaced(&mut self, interlaced: bool) {
+        self.interlaced_frame = interlaced.into();
+    }
+
+    fn is_top_field_first(&self) -> bool {
+        self.top_field_first != 0
+    }
+
+    fn set_top_field_first(&mut self, top_field_first: bool) {
+        self.top_field_first = top_field_first.into();
+    }
+}
+
+/// Channel layout of audio, `ch_layout`(FFmpeg 5.1+) or `channels` and
+/// `channel_layout`.
+pub trait ChannelLayoutExt {
+    fn nb_channels(&self) -> c_int;
+    /// Bitmask of the `AV_CH_*` channels, 0 if the layout is unknown or not
+    /// a native order layout.
+    fn channel_mask(&self) -> u64;
+    /// Set the default layout of `nb_channels` channels, e.g. stereo for 2
+    /// (`av_channel_layout_default`), fails if `nb_channels` isn't positive.
+    fn set_default_channel_layout(&mut self, nb_channels: c_int) -> Result<()>;
+    /// Set a native order layout of the `AV_CH_*` channels in `mask`
+    /// (`av_channel_layout_from_mask`), fails if `mask` is empty.
+    fn set_channel_mask(&mut self, mask: u64) -> Result<()>;
+}
+
+macro_rules! channel_layout_ext {
+    ($($ty:ident),*) => {$(
+        #[cfg(any(feature = "ffmpeg6", rusty_ffmpeg_prebuilt_binding))]
+        impl ChannelLayoutExt for ffi::$ty {
+            fn nb_channels(&self) -> c_int {
+                self.ch_layout.nb_channels
+            }
+
+            fn channel_mask(&self) -> u64 {
+                if self.ch_layout.order == ffi::AV_CHANNEL_ORDER_NATIVE {
+                    unsafe { self.ch_layout.u.mask }
+                } else {
+                    0
+                }
+            }
+
+            fn set_default_channel_layout(&mut self, nb_channels: c_int) -> Result<()> {
+                // av_channel_layout_default() returns nothing, and makes an
+                // invalid layout of non-positive counts.
+                if nb_channels <= 0 {
+                    return Err(Error::INVALID_ARGUMENT);
+                }
+                unsafe {
+                    ffi::av_channel_layout_uninit(&mut self.ch_layout);
+                    ffi::av_channel_layout_default(&mut self.ch_layout, nb_channels);
+                }
+                Ok(())
+            }
+
+            fn set_channel_mask(&mut self, mask: u64) -> Result<()> {
+                unsafe {
+                    ffi::av_channel_layout_uninit(&mut self.ch_layout);
+                    let ret = ffi::av_channel_layout_from_mask(&mut self.ch_layout, mask);
+                    crate::error::check(ret)?;
+                }
+                Ok(())
+            }
+        }
+
+        #[cfg(not(any(feature = "ffmpeg6", rusty_ffmpeg_prebuilt_binding)))]
+        impl ChannelLayoutExt for ffi::$ty {
+            fn nb_channels(&self) -> c_int {
+                self.channels
+            }
+
+            fn channel_mask(&self) -> u64 {
+                self.channel_layout
+            }
+
+            fn set_default_channel_layout(&mut self, nb_channels: c_int) -> Result<()> {
+                if nb_channels <= 0 {
+                    return Err(Error::INVALID_ARGUMENT);
+                }
+                self.channels = nb_channels;
+                self.channel_layout =
+                    unsafe { ffi::av_get_default_channel_layout(nb_channels) } as u64;
+                Ok(())
+            }
+
+            fn set_channel_mask(&mut self, mask: u64) -> Result<()> {
+                // Like av_channel_layout_from_mask().
+                if mask == 0 {
+                    return Err(Error::INVALID_ARGUMENT);
+                }
+                self.channels = mask.count_ones() as c_int;
+                self.channel_layout = mask;
+                Ok(())
+            }
+        }
+    )*};
+}
+
+channel_layout_ext!(AVFrame, AVCodecContext, AVCodecParameters);
+
+/// List terminated by `terminator`, e.g. `AVCodec.pix_fmts`.
+///
+/// # Safety
+/// `list` must be null or point to a terminated list living for `'static`.
+#[cfg(not(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding)))]
+unsafe fn terminated_list<T: Copy + PartialEq>(
+    list: *const T,
+    terminator: T,
+) -> Option<&'static [T]> {
+    if list.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while unsafe { *list.add(len) } != terminator {
+        len += 1;
+    }
+    Some(unsafe { std::slice::from_raw_parts(list, len) })
+}
+
+/// `avcodec_get_supported_config` with no codec context.
+#[cfg(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding))]
+fn supported_config<T>(
+    codec: &'static ffi::AVCodec,
+    config: ffi::AVCodecConfig,
+) -> Option<&'static [T]> {
+    use std::ptr;
+
+    let mut configs: *const std::ffi::c_void = ptr::null();
+    let mut len = 0;
+    let ret = unsafe {
+        ffi::avcodec_get_supported_config(ptr::null(), codec, config, 0, &mut configs, &mut len)
+    };
+    if ret < 0 || configs.is_null() {
+        None
+    } else {
+        Some(unsafe { std::slice::from_raw_parts(configs.cast(), len as usize) })
+    }
+}
+
+/// Pixel formats supported by the encoder, `None` if unknown(usually any
+/// format is accepted), `avcodec_get_supported_config`(FFmpeg 7.1+) or
+/// `AVCodec.pix_fmts`.
+pub fn supported_pix_fmts(codec: &'static ffi::AVCodec) -> Option<&'static [ffi::AVPixelFormat]> {
+    #[cfg(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding))]
+    return supported_config(codec, ffi::AV_CODEC_CONFIG_PIX_FORMAT);
+    #[cfg(not(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding)))]
+    return unsafe { terminated_list(codec.pix_fmts, ffi::AV_PIX_FMT_NONE) };
+}
+
+/// Sample formats supported by the encoder, `None` if unknown,
+/// `avcodec_get_supported_config`(FFmpeg 7.1+) or `AVCodec.sample_fmts`.
+pub fn supported_sample_fmts(
+    codec: &'static ffi::AVCodec,
+) -> Option<&'static [ffi::AVSampleFormat]> {
+    #[cfg(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding))]
+    return supported_config(codec, ffi::AV_CODEC_CONFIG_SAMPLE_FORMAT);
+    #[cfg(not(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding)))]
+    return unsafe { terminated_list(codec.sample_fmts, ffi::AV_SAMPLE_FMT_NONE) };
+}
+
+/// Sample rates supported by the encoder, `None` if unknown,
+/// `avcodec_get_supported_config`(FFmpeg 7.1+) or
+/// `AVCodec.supported_samplerates`.
+pub fn supported_sample_rates(codec: &'static ffi::AVCodec) -> Option<&'static [c_int]> {
+    #[cfg(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding))]
+    return supported_config(codec, ffi::AV_CODEC_CONFIG_SAMPLE_RATE);
+    #[cfg(not(any(feature = "ffmpeg7_1", rusty_ffmpeg_prebuilt_binding)))]
+    return unsafe { terminated_list(codec.supported_samplerates, 0) };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_flags() {
+        let mut frame: ffi::AVFrame = unsafe { std::mem::zeroed() };
+        assert!(!frame.is_key_frame());
+        frame.set_key_frame(true);
+        frame.set_interlaced(true);
+        assert!(frame.is_key_frame());
+        assert!(frame.is_interlaced());
+        assert!(!frame.is_top_field_first());
+        frame.set_key_frame(false);
+        assert!(!frame.is_key_frame());
+        assert!(frame.is_interlaced());
+    }
+
+    #[test]
+    fn test_default_channel_layout() {
+        let mut frame: ffi::AVFrame = unsafe { std::mem::zeroed() };
+        frame.set_default_channel_layout(2).unwrap();
+        assert_eq!(frame.nb_channels(), 2);
+        assert_eq!(frame.channel_mask().count_ones(), 2);
+        assert!(frame.set_default_channel_layout(0).is_err());
+    }
+
+    #[test]
+    fn test_channel_mask() {
+        let mut frame: ffi::AVFrame = unsafe { std::mem::zeroed() };
+        frame.set_channel_mask(0b111).unwrap();
+        assert_eq!(frame.nb_channels(), 3);
+        assert_eq!(frame.channel_mask(), 0b111);
+        assert!(frame.set_channel_mask(0).is_err());
+    }
+
+    #[test]
+    fn test_supported_pix_fmts() {
+        let codec = unsafe { ffi::avcodec_find_encoder_by_name(c"mjpeg".as_ptr()).as_ref() };
+        if let Some(codec) = codec {
+            assert!(supported_pix_fmts(codec).is_some_and(|x| !x.is_empty()));
+            assert!(!supported_pix_fmts(codec)
+                .unwrap()
+                .contains(&ffi::AV_PIX_FMT_NONE));
+        }
+    }
+}
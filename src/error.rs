@@ -0,0 +1,48 @@
+//! Error type of the safe wrappers.
+use crate::ffi;
+use std::ffi::c_int;
+use std::fmt;
+
+/// A negative `AVERROR` code returned by FFmpeg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Error(c_int);
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// `AVERROR(EAGAIN)`
+    pub const AGAIN: Error = Error(ffi::AVERROR(ffi::EAGAIN));
+    /// `AVERROR_EOF`
+    pub const EOF: Error = Error(ffi::AVERROR_EOF);
+    /// `AVERROR(EINVAL)`
+    pub const INVALID_ARGUMENT: Error = Error(ffi::AVERROR(ffi::EINVAL));
+    /// `AVERROR(ENOMEM)`
+    pub const OUT_OF_MEMORY: Error = Error(ffi::AVERROR(ffi::ENOMEM));
+
+    /// Wrap an `AVERROR` code.
+    pub const fn from_raw(code: c_int) -> Self {
+        Self(code)
+    }
+
+    /// The `AVERROR` code.
+    pub const fn code(self) -> c_int {
+        self.0
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&ffi::av_err2str(self.0))
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Convert the return value of FFmpeg functions, negative values are errors.
+pub(crate) fn check(ret: c_int) -> Result<c_int> {
+    if ret < 0 {
+        Err(Error(ret))
+    } else {
+        Ok(ret)
+    }
+}
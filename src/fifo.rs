@@ -0,0 +1,342 @@
+//! Typed FIFO of `Copy` elements built on `AVFifo`(`libavutil/fifo.h`).
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use std::any::Any;
+use std::ffi::{c_int, c_void};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::{self, NonNull};
+use std::slice;
+
+/// A FIFO of `T`, with a fixed capacity unless created by
+/// [`Fifo::with_auto_grow`].
+pub struct Fifo<T: Copy> {
+    fifo: NonNull<ffi::AVFifo>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Copy + Send> Send for Fifo<T> {}
+unsafe impl<T: Copy + Sync> Sync for Fifo<T> {}
+
+impl<T: Copy> Fifo<T> {
+    fn alloc(capacity: usize, flags: u32) -> Result<Self> {
+        // The buffer is allocated by `av_malloc`, which aligns to at least 16.
+        if mem::size_of::<T>() == 0 || mem::align_of::<T>() > 16 {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        let fifo = unsafe { ffi::av_fifo_alloc2(capacity, mem::size_of::<T>(), flags) };
+        Ok(Self {
+            fifo: NonNull::new(fifo).ok_or(Error::OUT_OF_MEMORY)?,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Allocate a FIFO of `capacity` elements, writing more than
+    /// [`Fifo::can_write`] elements fails.
+    pub fn new(capacity: usize) -> Result<Self> {
+        Self::alloc(capacity, 0)
+    }
+
+    /// Allocate a FIFO of `capacity` elements growing automatically on
+    /// writes(`AV_FIFO_FLAG_AUTO_GROW`) up to `max_elems` elements.
+    pub fn with_auto_grow(capacity: usize, max_elems: usize) -> Result<Self> {
+        let mut fifo = Self::alloc(capacity, ffi::AV_FIFO_FLAG_AUTO_GROW)?;
+        fifo.set_auto_grow_limit(max_elems);
+        Ok(fifo)
+    }
+
+    /// Set the size the FIFO can grow automatically to
+    /// (`av_fifo_auto_grow_limit`), no effect without auto grow.
+    pub fn set_auto_grow_limit(&mut self, max_elems: usize) {
+        unsafe { ffi::av_fifo_auto_grow_limit(self.fifo.as_ptr(), max_elems) };
+    }
+
+    /// Number of elements that can be read(`av_fifo_can_read`).
+    pub fn can_read(&self) -> usize {
+        unsafe { ffi::av_fifo_can_read(self.fifo.as_ptr()) }
+    }
+
+    /// Number of elements that can be written without growing
+    /// (`av_fifo_can_write`).
+    pub fn can_write(&self) -> usize {
+        unsafe { ffi::av_fifo_can_write(self.fifo.as_ptr()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.can_read() == 0
+    }
+
+    /// Make room for `additional` more elements(`av_fifo_grow2`), ignoring
+    /// the auto grow limit.
+    pub fn grow(&mut self, additional: usize) -> Result<()> {
+        check(unsafe { ffi::av_fifo_grow2(self.fifo.as_ptr(), additional) })?;
+        Ok(())
+    }
+
+    /// Append all the elements, or none of them if they don't fit
+    /// (`av_fifo_write`).
+    pub fn push_slice(&mut self, elems: &[T]) -> Result<()> {
+        check(unsafe {
+            ffi::av_fifo_write(self.fifo.as_ptr(), elems.as_ptr().cast(), elems.len())
+        })?;
+        Ok(())
+    }
+
+    pub fn push(&mut self, elem: T) -> Result<()> {
+        self.push_slice(slice::from_ref(&elem))
+    }
+
+    /// Move up to `buf.len()` elements into `buf`(`av_fifo_read`), returns
+    /// the number of elements read.
+    pub fn pop_into(&mut self, buf: &mut [T]) -> Result<usize> {
+        let len = buf.len().min(self.can_read());
+        check(unsafe { ffi::av_fifo_read(self.fifo.as_ptr(), buf.as_mut_ptr().cast(), len) })?;
+        Ok(len)
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let mut elem = MaybeUninit::<T>::uninit();
+        let ret = unsafe { ffi::av_fifo_read(self.fifo.as_ptr(), elem.as_mut_ptr().cast(), 1) };
+        (ret >= 0).then(|| unsafe { elem.assume_init() })
+    }
+
+    /// Copy up to `buf.len()` elements after the first `offset` elements
+    /// without removing them(`av_fifo_peek`), returns the number of
+    /// elements copied.
+    pub fn peek(&self, buf: &mut [T], offset: usize) -> Result<usize> {
+        let len = buf.len().min(self.can_read().saturating_sub(offset));
+        if len == 0 {
+            return Ok(0);
+        }
+        check(unsafe {
+            ffi::av_fifo_peek(self.fifo.as_ptr(), buf.as_mut_ptr().cast(), len, offset)
+        })?;
+        Ok(len)
+    }
+
+    /// Append up to `nb_elems` elements produced by `f` in place
+    /// (`av_fifo_write_from_cb`), returns the number of elements written.
+    /// Fails if `nb_elems` elements don't fit, as [`Fifo::push_slice`].
+    ///
+    /// `f` is called with the free space, possibly more than once as the
+    /// buffer wraps around, and returns the number of elements it filled.
+    /// Returning 0 or an error stops the write.
+    ///
+    /// # Safety
+    ///
+    /// When `f` returns `Ok(n)`, the first `n` elements of the slice passed
+    /// to it must have been initialized, they are readable from the FIFO
+    /// afterwards.
+    pub unsafe fn write_from_fn<F>(&mut self, nb_elems: usize, f: F) -> Result<usize>
+    where
+        F: FnMut(&mut [MaybeUninit<T>]) -> Result<usize>,
+    {
+        unsafe extern "C" fn read_cb<T, F>(
+            opaque: *mut c_void,
+            buf: *mut c_void,
+            nb_elems: *mut usize,
+        ) -> c_int
+        where
+            F: FnMut(&mut [MaybeUninit<T>]) -> Result<usize>,
+        {
+            let callback = unsafe { &mut *opaque.cast::<Callback<F>>() };
+            let buf = unsafe { slice::from_raw_parts_mut(buf.cast(), *nb_elems) };
+            callback.call(nb_elems, |f| f(buf))
+        }
+
+        let mut callback = Callback::new(f);
+        let mut len = nb_elems;
+        let ret = unsafe {
+            ffi::av_fifo_write_from_cb(
+                self.fifo.as_ptr(),
+                Some(read_cb::<T, F>),
+                ptr::addr_of_mut!(callback).cast(),
+                &mut len,
+            )
+        };
+        callback.finish(ret, len)
+    }
+
+    /// Remove up to `nb_elems` elements, passing them to `f` in place
+    /// (`av_fifo_read_to_cb`), returns the number of elements read.
+    ///
+    /// `f` is called with the readable elements, possibly more than once as
+    /// the buffer wraps around, and returns the number of elements it
+    /// consumed. Returning 0 or an error stops the read.
+    pub fn read_to_fn<F>(&mut self, nb_elems: usize, f: F) -> Result<usize>
+    where
+        F: FnMut(&[T]) -> Result<usize>,
+    {
+        unsafe extern "C" fn write_cb<T, F>(
+            opaque: *mut c_void,
+            buf: *mut c_void,
+            nb_elems: *mut usize,
+        ) -> c_int
+        where
+            F: FnMut(&[T]) -> Result<usize>,
+        {
+            let callback = unsafe { &mut *opaque.cast::<Callback<F>>() };
+            let buf = unsafe { slice::from_raw_parts(buf.cast::<T>(), *nb_elems) };
+            callback.call(nb_elems, |f| f(buf))
+        }
+
+        let mut callback = Callback::new(f);
+        let mut len = nb_elems.min(self.can_read());
+        let ret = unsafe {
+            ffi::av_fifo_read_to_cb(
+                self.fifo.as_ptr(),
+                Some(write_cb::<T, F>),
+                ptr::addr_of_mut!(callback).cast(),
+                &mut len,
+            )
+        };
+        callback.finish(ret, len)
+    }
+
+    /// Discard up to `nb_elems` elements(`av_fifo_drain2`).
+    pub fn drain(&mut self, nb_elems: usize) {
+        let nb_elems = nb_elems.min(self.can_read());
+        unsafe { ffi::av_fifo_drain2(self.fifo.as_ptr(), nb_elems) };
+    }
+
+    /// Discard all the elements(`av_fifo_reset2`).
+    pub fn clear(&mut self) {
+        unsafe { ffi::av_fifo_reset2(self.fifo.as_ptr()) };
+    }
+}
+
+impl<T: Copy> Drop for Fifo<T> {
+    fn drop(&mut self) {
+        let mut fifo = self.fifo.as_ptr();
+        unsafe { ffi::av_fifo_freep2(&mut fifo) };
+    }
+}
+
+impl<T: Copy> fmt::Debug for Fifo<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fifo")
+            .field("can_read", &self.can_read())
+            .field("can_write", &self.can_write())
+            .finish()
+    }
+}
+
+/// A closure passed through the `opaque` of the `av_fifo_*_cb` functions,
+/// with the panic caught in it to be resumed after returning to Rust.
+struct Callback<F> {
+    f: F,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<F> Callback<F> {
+    fn new(f: F) -> Self {
+        Self { f, panic: None }
+    }
+
+    /// Call `f` and store the number of processed elements, capped to the
+    /// number offered, in `nb_elems`.
+    unsafe fn call(
+        &mut self,
+        nb_elems: *mut usize,
+        call: impl FnOnce(&mut F) -> Result<usize>,
+    ) -> c_int {
+        let f = &mut self.f;
+        match panic::catch_unwind(AssertUnwindSafe(|| call(f))) {
+            Ok(Ok(len)) => {
+                unsafe { *nb_elems = len.min(*nb_elems) };
+                0
+            }
+            Ok(Err(err)) => err.code(),
+            Err(payload) => {
+                self.panic = Some(payload);
+                ffi::AVERROR_EXTERNAL
+            }
+        }
+    }
+
+    fn finish(self, ret: c_int, len: usize) -> Result<usize> {
+        if let Some(payload) = self.panic {
+            panic::resume_unwind(payload);
+        }
+        check(ret)?;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fifo() {
+        let mut fifo = Fifo::<u32>::new(4).unwrap();
+        fifo.push_slice(&[1, 2, 3]).unwrap();
+        assert!(fifo.push_slice(&[4, 5]).is_err());
+        assert_eq!(fifo.can_read(), 3);
+        let mut buf = [0; 2];
+        assert_eq!(fifo.peek(&mut buf, 1), Ok(2));
+        assert_eq!(buf, [2, 3]);
+        assert_eq!(fifo.pop(), Some(1));
+        assert_eq!(fifo.pop_into(&mut buf), Ok(2));
+        assert_eq!(fifo.pop(), None);
+
+        // Wraps around the end of the buffer.
+        fifo.push_slice(&[4, 5]).unwrap();
+        let mut next = 6;
+        let written = unsafe {
+            fifo.write_from_fn(2, |buf| {
+                for elem in buf.iter_mut() {
+                    elem.write(next);
+                    next += 1;
+                }
+                Ok(buf.len())
+            })
+        };
+        assert_eq!(written, Ok(2));
+        let mut read = Vec::new();
+        let len = fifo.read_to_fn(usize::MAX, |buf| {
+            read.extend_from_slice(buf);
+            Ok(buf.len())
+        });
+        assert_eq!(len, Ok(4));
+        assert_eq!(read, [4, 5, 6, 7]);
+
+        let mut fifo = Fifo::<u8>::with_auto_grow(1, 3).unwrap();
+        fifo.push_slice(&[1, 2, 3]).unwrap();
+        assert!(fifo.push(4).is_err());
+        fifo.drain(2);
+        assert_eq!(fifo.pop(), Some(3));
+    }
+
+    #[test]
+    fn test_write_from_fn_wrap_around() {
+        let mut fifo = Fifo::<u32>::new(4).unwrap();
+        fifo.push_slice(&[1, 2, 3]).unwrap();
+        fifo.drain(3);
+        // The free space is the last element then the first two.
+        let mut calls = Vec::new();
+        let mut next = 10;
+        let written = unsafe {
+            fifo.write_from_fn(3, |buf| {
+                calls.push(buf.len());
+                for elem in buf.iter_mut() {
+                    elem.write(next);
+                    next += 1;
+                }
+                Ok(buf.len())
+            })
+        };
+        assert_eq!(written, Ok(3));
+        assert_eq!(calls, [1, 2]);
+        let mut buf = [0; 3];
+        assert_eq!(fifo.pop_into(&mut buf), Ok(3));
+        assert_eq!(buf, [10, 11, 12]);
+
+        // Filling nothing stops the write.
+        let written = unsafe { fifo.write_from_fn(3, |_| Ok(0)) };
+        assert_eq!(written, Ok(0));
+        assert!(fifo.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Owned `AVFrame`.
+use crate::error::{Error, Result};
+use crate::ffi;
+use std::ffi::c_int;
+use std::fmt;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+/// An `AVFrame` allocated by `av_frame_alloc` and freed by `av_frame_free`,
+/// which derefs to the `AVFrame`. The fields describing the buffers are
+/// read-only, see [`Frame::as_mut_raw`].
+pub struct Frame(NonNull<ffi::AVFrame>);
+
+unsafe impl Send for Frame {}
+
+impl Frame {
+    /// Allocate an empty frame without buffers.
+    pub fn new() -> Result<Self> {
+        NonNull::new(unsafe { ffi::av_frame_alloc() })
+            .map(Self)
+            .ok_or(Error::OUT_OF_MEMORY)
+    }
+
+    /// Take the ownership of a frame.
+    ///
+    /// # Safety
+    /// `frame` must be allocated by `av_frame_alloc` and not be used
+    /// elsewhere.
+    pub unsafe fn from_raw(frame: NonNull<ffi::AVFrame>) -> Self {
+        Self(frame)
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVFrame {
+        self.0.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVFrame {
+        self.0.as_ptr()
+    }
+
+    /// Give up the ownership, the frame should be freed by `av_frame_free`.
+    pub fn into_raw(self) -> NonNull<ffi::AVFrame> {
+        let frame = self.0;
+        std::mem::forget(self);
+        frame
+    }
+
+    /// Mutable access to all the fields.
+    ///
+    /// # Safety
+    /// The fields describing the buffers(e.g. `format`, `nb_samples`,
+    /// `width`, `data`, `extended_data`) must stay consistent with them,
+    /// since safe code relies on them to access the buffers.
+    pub unsafe fn as_mut_raw(&mut self) -> &mut ffi::AVFrame {
+        unsafe { self.0.as_mut() }
+    }
+
+    pub fn set_pts(&mut self, pts: i64) {
+        unsafe { self.0.as_mut().pts = pts };
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: c_int) {
+        unsafe { self.0.as_mut().sample_rate = sample_rate };
+    }
+
+    /// New reference to the same buffers(`av_frame_clone`).
+    pub fn try_clone(&self) -> Result<Self> {
+        NonNull::new(unsafe { ffi::av_frame_clone(self.as_ptr()) })
+            .map(Self)
+            .ok_or(Error::OUT_OF_MEMORY)
+    }
+
+    /// Unreference the buffers and reset the fields(`av_frame_unref`).
+    pub fn unref(&mut self) {
+        unsafe { ffi::av_frame_unref(self.as_mut_ptr()) };
+    }
+}
+
+impl Deref for Frame {
+    type Target = ffi::AVFrame;
+
+    fn deref(&self) -> &ffi::AVFrame {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let mut frame = self.0.as_ptr();
+        unsafe { ffi::av_frame_free(&mut frame) };
+    }
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("nb_samples", &self.nb_samples)
+            .field("pts", &self.pts)
+            .finish()
+    }
+}
@@ -0,0 +1,23 @@
+//! Compare the struct layouts and constants of the Rust side with the values
+//! of the FFmpeg headers, which are compiled into a C probe by build.rs.
+include!(concat!(env!("OUT_DIR"), "/header_probe.rs"));
+
+#[test]
+fn test_headers() {
+    let items = header_probe();
+    if items.is_empty() {
+        eprintln!("FFmpeg headers are not found, skipped.");
+        return;
+    }
+    let mismatches: Vec<String> = items
+        .into_iter()
+        .filter_map(|(name, rust_value, c_value)| match c_value {
+            None => Some(format!("{name}: not defined in the headers")),
+            Some(c_value) if c_value != rust_value => {
+                Some(format!("{name}: Rust {rust_value:#x}, C {c_value:#x}"))
+            }
+            Some(_) => None,
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "\n{}", mismatches.join("\n"));
+}
@@ -0,0 +1,257 @@
+//! Discover the hardware acceleration compiled into the linked FFmpeg, which
+//! doesn't require the hardware to be present.
+use crate::error::{check, Error};
+use crate::ffi;
+use std::ffi::{c_int, CStr, CString};
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+use std::ptr::{self, NonNull};
+
+/// Iterate the device types the linked FFmpeg is built with
+/// (`av_hwdevice_iterate_types`).
+pub fn device_types() -> impl Iterator<Item = ffi::AVHWDeviceType> {
+    let mut prev = ffi::AV_HWDEVICE_TYPE_NONE;
+    std::iter::from_fn(move || {
+        prev = unsafe { ffi::av_hwdevice_iterate_types(prev) };
+        (prev != ffi::AV_HWDEVICE_TYPE_NONE).then_some(prev)
+    })
+}
+
+/// Name of a device type, e.g. `"vaapi"` (`av_hwdevice_get_type_name`).
+pub fn device_type_name(device_type: ffi::AVHWDeviceType) -> Option<&'static str> {
+    let name = unsafe { ffi::av_hwdevice_get_type_name(device_type) };
+    if name.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(name) }.to_str().ok()
+    }
+}
+
+/// Find a device type by its name (`av_hwdevice_find_type_by_name`).
+pub fn find_device_type(name: &str) -> Option<ffi::AVHWDeviceType> {
+    let name = CString::new(name).ok()?;
+    let device_type = unsafe { ffi::av_hwdevice_find_type_by_name(name.as_ptr()) };
+    (device_type != ffi::AV_HWDEVICE_TYPE_NONE).then_some(device_type)
+}
+
+/// Set of `AV_CODEC_HW_CONFIG_METHOD_*` flags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HwConfigMethods(c_int);
+
+impl HwConfigMethods {
+    /// `AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX`
+    pub const HW_DEVICE_CTX: Self = Self(ffi::AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as c_int);
+    /// `AV_CODEC_HW_CONFIG_METHOD_HW_FRAMES_CTX`
+    pub const HW_FRAMES_CTX: Self = Self(ffi::AV_CODEC_HW_CONFIG_METHOD_HW_FRAMES_CTX as c_int);
+    /// `AV_CODEC_HW_CONFIG_METHOD_INTERNAL`
+    pub const INTERNAL: Self = Self(ffi::AV_CODEC_HW_CONFIG_METHOD_INTERNAL as c_int);
+    /// `AV_CODEC_HW_CONFIG_METHOD_AD_HOC`
+    pub const AD_HOC: Self = Self(ffi::AV_CODEC_HW_CONFIG_METHOD_AD_HOC as c_int);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn from_bits(bits: c_int) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> c_int {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for HwConfigMethods {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for HwConfigMethods {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A hardware configuration of a codec (`AVCodecHWConfig`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HwConfig {
+    /// The hardware pixel format the decoder outputs, or the pixel format the
+    /// encoder accepts.
+    pub pix_fmt: ffi::AVPixelFormat,
+    pub device_type: ffi::AVHWDeviceType,
+    pub methods: HwConfigMethods,
+}
+
+/// Iterate the hardware configurations of a codec (`avcodec_get_hw_config`).
+pub fn codec_hw_configs(codec: &'static ffi::AVCodec) -> impl Iterator<Item = HwConfig> {
+    (0..).map_while(move |i| {
+        let config = unsafe { ffi::avcodec_get_hw_config(codec, i).as_ref() }?;
+        Some(HwConfig {
+            pix_fmt: config.pix_fmt,
+            device_type: config.device_type,
+            methods: HwConfigMethods(config.methods),
+        })
+    })
+}
+
+/// A decoder supporting hardware acceleration.
+#[derive(Debug, Clone)]
+pub struct HwDecoder {
+    pub codec: &'static ffi::AVCodec,
+    pub configs: Vec<HwConfig>,
+}
+
+impl HwDecoder {
+    pub fn name(&self) -> &'static str {
+        unsafe { CStr::from_ptr(self.codec.name) }
+            .to_str()
+            .unwrap_or_default()
+    }
+}
+
+/// Iterate the decoders of the linked FFmpeg which have at least one hardware
+/// configuration.
+pub fn hw_decoders() -> impl Iterator<Item = HwDecoder> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe { ffi::av_codec_iterate(&mut opaque).as_ref() })
+        .filter(|codec| unsafe { ffi::av_codec_is_decoder(*codec) } != 0)
+        .filter_map(|codec| {
+            let configs: Vec<_> = codec_hw_configs(codec).collect();
+            (!configs.is_empty()).then_some(HwDecoder { codec, configs })
+        })
+}
+
+/// Error of creating a [`HwDeviceContext`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwDeviceError {
+    /// The linked FFmpeg is not built with this device type.
+    Unsupported(ffi::AVHWDeviceType),
+    /// The device type is supported, but the device cannot be opened, e.g.
+    /// there is no such hardware or driver on this machine.
+    NotAvailable(Error),
+}
+
+impl fmt::Display for HwDeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HwDeviceError::Unsupported(device_type) => write!(
+                f,
+                "hardware device type `{}` is not supported by the linked FFmpeg",
+                device_type_name(*device_type).unwrap_or("unknown")
+            ),
+            HwDeviceError::NotAvailable(e) => write!(f, "hardware device is not available: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HwDeviceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HwDeviceError::Unsupported(_) => None,
+            HwDeviceError::NotAvailable(e) => Some(e),
+        }
+    }
+}
+
+/// Reference to an `AVHWDeviceContext`.
+pub struct HwDeviceContext(NonNull<ffi::AVBufferRef>);
+
+unsafe impl Send for HwDeviceContext {}
+
+impl HwDeviceContext {
+    /// Open a device of the given type (`av_hwdevice_ctx_create`). `device`
+    /// is type specific, e.g. `/dev/dri/renderD128` for VAAPI, `None` picks
+    /// the default device.
+    pub fn create(
+        device_type: ffi::AVHWDeviceType,
+        device: Option<&str>,
+    ) -> Result<Self, HwDeviceError> {
+        if !device_types().any(|x| x == device_type) {
+            return Err(HwDeviceError::Unsupported(device_type));
+        }
+        let device = device
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| HwDeviceError::NotAvailable(Error::INVALID_ARGUMENT))?;
+        let mut device_ctx = ptr::null_mut();
+        check(unsafe {
+            ffi::av_hwdevice_ctx_create(
+                &mut device_ctx,
+                device_type,
+                device.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                ptr::null_mut(),
+                0,
+            )
+        })
+        .map_err(HwDeviceError::NotAvailable)?;
+        NonNull::new(device_ctx)
+            .map(Self)
+            .ok_or(HwDeviceError::NotAvailable(Error::OUT_OF_MEMORY))
+    }
+
+    pub fn device_type(&self) -> ffi::AVHWDeviceType {
+        unsafe { (*(self.0.as_ref().data as *const ffi::AVHWDeviceContext)).type_ }
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVBufferRef {
+        self.0.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVBufferRef {
+        self.0.as_ptr()
+    }
+
+    /// Give up the ownership of the reference, e.g. for
+    /// `AVCodecContext.hw_device_ctx`.
+    pub fn into_raw(self) -> *mut ffi::AVBufferRef {
+        let ptr = self.0.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+
+    /// New reference to the same device (`av_buffer_ref`).
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let buf = unsafe { ffi::av_buffer_ref(self.0.as_ptr()) };
+        NonNull::new(buf).map(Self).ok_or(Error::OUT_OF_MEMORY)
+    }
+}
+
+impl Drop for HwDeviceContext {
+    fn drop(&mut self) {
+        let mut buf = self.0.as_ptr();
+        unsafe { ffi::av_buffer_unref(&mut buf) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_type_names() {
+        for device_type in device_types() {
+            let name = device_type_name(device_type).unwrap();
+            assert_eq!(find_device_type(name), Some(device_type));
+        }
+        assert_eq!(find_device_type("no_such_device"), None);
+    }
+
+    #[test]
+    fn test_create_unsupported() {
+        assert_eq!(
+            HwDeviceContext::create(ffi::AV_HWDEVICE_TYPE_NONE, None).err(),
+            Some(HwDeviceError::Unsupported(ffi::AV_HWDEVICE_TYPE_NONE))
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Images of up to 4 planes built on `libavutil/imgutils.h`.
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use crate::pixel_format::PixelFormat;
+use std::ffi::{c_int, c_uint};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+use std::slice;
+
+/// Check that an image of the size can be allocated and its planes can be
+/// addressed(`av_image_check_size2`).
+pub fn check_size(format: PixelFormat, width: usize, height: usize) -> Result<()> {
+    let (width, height) = (
+        c_uint::try_from(width).map_err(|_| Error::INVALID_ARGUMENT)?,
+        c_uint::try_from(height).map_err(|_| Error::INVALID_ARGUMENT)?,
+    );
+    check(unsafe {
+        ffi::av_image_check_size2(width, height, i64::MAX, format.as_raw(), 0, ptr::null_mut())
+    })?;
+    Ok(())
+}
+
+/// Size of the image with the rows aligned to `align` bytes
+/// (`av_image_get_buffer_size`), 1 for tightly packed.
+pub fn buffer_size(
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    align: usize,
+) -> Result<usize> {
+    check_size(format, width, height)?;
+    let size = check(unsafe {
+        ffi::av_image_get_buffer_size(
+            format.as_raw(),
+            width as c_int,
+            height as c_int,
+            align_c_int(align)?,
+        )
+    })?;
+    Ok(size as usize)
+}
+
+fn align_c_int(align: usize) -> Result<c_int> {
+    if align.is_power_of_two() {
+        c_int::try_from(align).map_err(|_| Error::INVALID_ARGUMENT)
+    } else {
+        Err(Error::INVALID_ARGUMENT)
+    }
+}
+
+/// Sizes of the planes in bytes, the palette of paletted formats excluded.
+fn plane_sizes(
+    format: PixelFormat,
+    height: usize,
+    linesize: &[c_int; 4],
+) -> impl Iterator<Item = usize> {
+    let (_, log2_chroma_h) = format.chroma_shift();
+    let linesize = *linesize;
+    (0..format.planes()).map(move |plane| {
+        let height = if plane == 1 || plane == 2 {
+            // AV_CEIL_RSHIFT
+            (height + (1 << log2_chroma_h) - 1) >> log2_chroma_h
+        } else {
+            height
+        };
+        linesize[plane].max(0) as usize * height
+    })
+}
+
+/// An image allocated by `av_image_alloc`.
+pub struct Image {
+    data: [*mut u8; 4],
+    linesize: [c_int; 4],
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+}
+
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Image {
+    /// Allocate a zeroed image with the rows of each plane aligned to
+    /// `align` bytes, e.g. 32 or 64 for SIMD, 1 for tightly packed.
+    pub fn new(format: PixelFormat, width: usize, height: usize, align: usize) -> Result<Self> {
+        check_size(format, width, height)?;
+        let mut data = [ptr::null_mut(); 4];
+        let mut linesize = [0; 4];
+        let size = check(unsafe {
+            ffi::av_image_alloc(
+                data.as_mut_ptr(),
+                linesize.as_mut_ptr(),
+                width as c_int,
+                height as c_int,
+                format.as_raw(),
+                align_c_int(align)?,
+            )
+        })?;
+        // All the planes are in a single allocation starting at `data[0]`.
+        unsafe { ptr::write_bytes(data[0], 0, size as usize) };
+        Ok(Self {
+            data,
+            linesize,
+            format,
+            width,
+            height,
+        })
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Bytes per row of each plane, including the alignment padding.
+    pub fn linesize(&self) -> [c_int; 4] {
+        self.linesize
+    }
+
+    /// The image borrowed as an [`ImageRef`].
+    pub fn as_image_ref(&self) -> ImageRef<'_> {
+        ImageRef {
+            data: self.data.map(|plane| plane.cast_const()),
+            linesize: self.linesize,
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The planes, `linesize()[i] * rows` bytes each.
+    pub fn planes(&self) -> Vec<&[u8]> {
+        self.as_image_ref().planes()
+    }
+
+    pub fn planes_mut(&mut self) -> Vec<&mut [u8]> {
+        plane_sizes(self.format, self.height, &self.linesize)
+            .enumerate()
+            .map(|(i, size)| unsafe { slice::from_raw_parts_mut(self.data[i], size) })
+            .collect()
+    }
+
+    /// Copy the content of an image of the same format and size
+    /// (`av_image_copy`).
+    pub fn copy_from(&mut self, src: &ImageRef<'_>) -> Result<()> {
+        if (src.format, src.width, src.height) != (self.format, self.width, self.height) {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        unsafe {
+            ffi::av_image_copy(
+                self.data.as_mut_ptr() as _,
+                self.linesize.as_mut_ptr() as _,
+                src.data.as_ptr() as _,
+                src.linesize.as_ptr(),
+                self.format.as_raw(),
+                self.width as c_int,
+                self.height as c_int,
+            )
+        };
+        Ok(())
+    }
+
+    /// Copy into a tightly packed buffer(`av_image_copy_to_buffer`).
+    pub fn to_packed_vec(&self) -> Result<Vec<u8>> {
+        self.as_image_ref().to_packed_vec()
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe { ffi::av_freep(self.data.as_mut_ptr().cast()) };
+    }
+}
+
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Image")
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("linesize", &self.linesize)
+            .finish()
+    }
+}
+
+/// A borrowed image, e.g. a packed image in a Rust `&[u8]`.
+#[derive(Clone, Copy)]
+pub struct ImageRef<'a> {
+    data: [*const u8; 4],
+    linesize: [c_int; 4],
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    _marker: PhantomData<&'a [u8]>,
+}
+
+unsafe impl Send for ImageRef<'_> {}
+unsafe impl Sync for ImageRef<'_> {}
+
+impl<'a> ImageRef<'a> {
+    /// Wrap an image stored in `buf` with the rows aligned to `align` bytes,
+    /// 1 for tightly packed(`av_image_fill_arrays`). Fails if `buf` is too
+    /// small.
+    pub fn new(
+        buf: &'a [u8],
+        format: PixelFormat,
+        width: usize,
+        height: usize,
+        align: usize,
+    ) -> Result<Self> {
+        if buf.len() < buffer_size(format, width, height, align)? {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        let mut data = [ptr::null_mut(); 4];
+        let mut linesize = [0; 4];
+        check(unsafe {
+            ffi::av_image_fill_arrays(
+                data.as_mut_ptr(),
+                linesize.as_mut_ptr(),
+                buf.as_ptr(),
+                format.as_raw(),
+                width as c_int,
+                height as c_int,
+                align as c_int,
+            )
+        })?;
+        Ok(Self {
+            data: data.map(|plane| plane.cast_const()),
+            linesize,
+            format,
+            width,
+            height,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn linesize(&self) -> [c_int; 4] {
+        self.linesize
+    }
+
+    /// The planes, `linesize()[i] * rows` bytes each.
+    pub fn planes(&self) -> Vec<&'a [u8]> {
+        plane_sizes(self.format, self.height, &self.linesize)
+            .enumerate()
+            .map(|(i, size)| unsafe { slice::from_raw_parts(self.data[i], size) })
+            .collect()
+    }
+
+    /// Copy into a tightly packed buffer(`av_image_copy_to_buffer`).
+    pub fn to_packed_vec(&self) -> Result<Vec<u8>> {
+        let size = buffer_size(self.format, self.width, self.height, 1)?;
+        let mut buf = vec![0; size];
+        check(unsafe {
+            ffi::av_image_copy_to_buffer(
+                buf.as_mut_ptr(),
+                size as c_int,
+                self.data.as_ptr(),
+                self.linesize.as_ptr(),
+                self.format.as_raw(),
+                self.width as c_int,
+                self.height as c_int,
+                1,
+            )
+        })?;
+        Ok(buf)
+    }
+}
+
+impl fmt::Debug for ImageRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageRef")
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("linesize", &self.linesize)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_image() {
+        let yuv420p = PixelFormat::from(ffi::AV_PIX_FMT_YUV420P);
+        assert_eq!(buffer_size(yuv420p, 5, 3, 1), Ok(15 + 2 * 3 * 2));
+        assert!(check_size(yuv420p, 0, 3).is_err());
+        assert!(Image::new(yuv420p, 5, 3, 3).is_err());
+
+        let packed: Vec<u8> = (0..27).collect();
+        assert!(ImageRef::new(&packed[..26], yuv420p, 5, 3, 1).is_err());
+        let image_ref = ImageRef::new(&packed, yuv420p, 5, 3, 1).unwrap();
+        assert_eq!(image_ref.planes()[1], &[15, 16, 17, 18, 19, 20]);
+
+        let mut image = Image::new(yuv420p, 5, 3, 32).unwrap();
+        assert_eq!(image.linesize()[0], 32);
+        assert!(image
+            .planes()
+            .iter()
+            .all(|plane| plane.iter().all(|x| *x == 0)));
+        image.copy_from(&image_ref).unwrap();
+        assert_eq!(&image.planes()[0][32..37], &[5, 6, 7, 8, 9]);
+        assert_eq!(image.to_packed_vec().unwrap(), packed);
+    }
+}
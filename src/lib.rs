@@ -1,4 +1,30 @@
+pub mod audio_fifo;
 mod avutil;
+pub mod bitstream_filter;
+pub mod compat;
+pub mod error;
+#[cfg(any(feature = "ffmpeg6", rusty_ffmpeg_prebuilt_binding))]
+pub mod fifo;
+pub mod frame;
+#[cfg(all(test, feature = "verify_headers"))]
+mod header_probe;
+pub mod hwcontext;
+pub mod image;
+pub mod packet;
+pub mod parser;
+pub mod pixel_format;
+pub mod probe;
+pub mod registry;
+pub mod sample_format;
+pub mod seek;
+#[cfg(feature = "typed_enums")]
+pub mod typed_enums;
+
+#[cfg(all(feature = "typed_enums", feature = "use_prebuilt_binding"))]
+compile_error!("`typed_enums` needs bindings generated at build time, which conflicts with `use_prebuilt_binding`.");
+
+#[cfg(all(feature = "wrap_static_fns", feature = "use_prebuilt_binding"))]
+compile_error!("`wrap_static_fns` needs bindings generated at build time, which conflicts with `use_prebuilt_binding`.");
 
 #[allow(
     non_snake_case,
@@ -9,8 +35,12 @@ mod avutil;
     clippy::all
 )]
 pub mod ffi {
-    #[cfg(feature = "ffmpeg6")]
+    // Generated from the headers by build.rs when bindings are generated at
+    // build time.
+    #[cfg(all(feature = "ffmpeg6", not(rusty_ffmpeg_header_macros)))]
     pub use crate::avutil::channel_layout::*;
-    pub use crate::avutil::{_avutil::*, common::*, error::*, pixfmt::*, rational::*};
+    #[cfg(not(rusty_ffmpeg_header_macros))]
+    pub use crate::avutil::pixfmt::*;
+    pub use crate::avutil::{_avutil::*, common::*, error::*, rational::*};
     include!(concat!(env!("OUT_DIR"), "/binding.rs"));
 }
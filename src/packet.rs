@@ -0,0 +1,134 @@
+//! Owned `AVPacket`.
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use std::ffi::c_int;
+use std::fmt;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::slice;
+
+/// An `AVPacket` allocated by `av_packet_alloc` and freed by
+/// `av_packet_free`, which derefs to the `AVPacket`. The fields describing
+/// the data are read-only, see [`Packet::as_mut_raw`].
+pub struct Packet(NonNull<ffi::AVPacket>);
+
+unsafe impl Send for Packet {}
+
+impl Packet {
+    /// Allocate an empty packet without data.
+    pub fn new() -> Result<Self> {
+        NonNull::new(unsafe { ffi::av_packet_alloc() })
+            .map(Self)
+            .ok_or(Error::OUT_OF_MEMORY)
+    }
+
+    /// Allocate a packet with a copy of `data`(`av_new_packet`).
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let size = c_int::try_from(data.len()).map_err(|_| Error::INVALID_ARGUMENT)?;
+        let mut packet = Self::new()?;
+        check(unsafe { ffi::av_new_packet(packet.as_mut_ptr(), size) })?;
+        if !data.is_empty() {
+            unsafe { slice::from_raw_parts_mut((*packet.as_mut_ptr()).data, data.len()) }
+                .copy_from_slice(data);
+        }
+        Ok(packet)
+    }
+
+    /// Take the ownership of a packet.
+    ///
+    /// # Safety
+    /// `packet` must be allocated by `av_packet_alloc` and not be used
+    /// elsewhere.
+    pub unsafe fn from_raw(packet: NonNull<ffi::AVPacket>) -> Self {
+        Self(packet)
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVPacket {
+        self.0.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVPacket {
+        self.0.as_ptr()
+    }
+
+    /// Give up the ownership, the packet should be freed by
+    /// `av_packet_free`.
+    pub fn into_raw(self) -> NonNull<ffi::AVPacket> {
+        let packet = self.0;
+        std::mem::forget(self);
+        packet
+    }
+
+    /// Mutable access to all the fields.
+    ///
+    /// # Safety
+    /// `data`, `size` and `buf` must stay consistent, since safe code relies
+    /// on them to access the payload.
+    pub unsafe fn as_mut_raw(&mut self) -> &mut ffi::AVPacket {
+        unsafe { self.0.as_mut() }
+    }
+
+    pub fn set_pts(&mut self, pts: i64) {
+        unsafe { self.0.as_mut().pts = pts };
+    }
+
+    pub fn set_dts(&mut self, dts: i64) {
+        unsafe { self.0.as_mut().dts = dts };
+    }
+
+    pub fn set_stream_index(&mut self, stream_index: c_int) {
+        unsafe { self.0.as_mut().stream_index = stream_index };
+    }
+
+    pub fn set_flags(&mut self, flags: c_int) {
+        unsafe { self.0.as_mut().flags = flags };
+    }
+
+    /// New reference to the same data(`av_packet_clone`).
+    pub fn try_clone(&self) -> Result<Self> {
+        NonNull::new(unsafe { ffi::av_packet_clone(self.as_ptr()) })
+            .map(Self)
+            .ok_or(Error::OUT_OF_MEMORY)
+    }
+
+    /// The payload, empty if there is no data.
+    pub fn data(&self) -> &[u8] {
+        if self.data.is_null() || self.size <= 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.data, self.size as usize) }
+        }
+    }
+
+    /// Unreference the data and reset the fields(`av_packet_unref`).
+    pub fn unref(&mut self) {
+        unsafe { ffi::av_packet_unref(self.as_mut_ptr()) };
+    }
+}
+
+impl Deref for Packet {
+    type Target = ffi::AVPacket;
+
+    fn deref(&self) -> &ffi::AVPacket {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Drop for Packet {
+    fn drop(&mut self) {
+        let mut packet = self.0.as_ptr();
+        unsafe { ffi::av_packet_free(&mut packet) };
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("stream_index", &self.stream_index)
+            .field("size", &self.size)
+            .field("pts", &self.pts)
+            .field("dts", &self.dts)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
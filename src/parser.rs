@@ -0,0 +1,231 @@
+//! Split elementary streams, e.g. raw `.h264` or ADTS `.aac`, into packets
+//! (`av_parser_parse2`).
+use crate::error::{Error, Result};
+use crate::ffi;
+use crate::packet::Packet;
+use std::ffi::c_int;
+use std::fmt;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+/// A parser of an elementary stream(`AVCodecParserContext`), fed with
+/// chunks of any size and yielding a packet per frame.
+pub struct Parser {
+    parser: NonNull<ffi::AVCodecParserContext>,
+    codec_ctx: NonNull<ffi::AVCodecContext>,
+    /// The input with `AV_INPUT_BUFFER_PADDING_SIZE` zeroed bytes after it.
+    buf: Vec<u8>,
+}
+
+unsafe impl Send for Parser {}
+
+impl Parser {
+    /// Create a parser(`av_parser_init`), fails if there is no parser for
+    /// the codec.
+    pub fn new(codec_id: ffi::AVCodecID) -> Result<Self> {
+        #[cfg(feature = "typed_enums")]
+        let codec_id = codec_id.0 as c_int;
+        #[cfg(not(feature = "typed_enums"))]
+        let codec_id = codec_id as c_int;
+        let parser = NonNull::new(unsafe { ffi::av_parser_init(codec_id) })
+            .ok_or(Error::INVALID_ARGUMENT)?;
+        let Some(codec_ctx) = NonNull::new(unsafe { ffi::avcodec_alloc_context3(ptr::null()) })
+        else {
+            unsafe { ffi::av_parser_close(parser.as_ptr()) };
+            return Err(Error::OUT_OF_MEMORY);
+        };
+        Ok(Self {
+            parser,
+            codec_ctx,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::AVCodecParserContext {
+        self.parser.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::AVCodecParserContext {
+        self.parser.as_ptr()
+    }
+
+    /// The codec context the parser fills, e.g. with the sample rate of
+    /// audio streams.
+    pub fn codec_context(&self) -> &ffi::AVCodecContext {
+        unsafe { self.codec_ctx.as_ref() }
+    }
+
+    /// Parse a chunk of the stream, returning the packets completed by it.
+    ///
+    /// `pts`, `dts` and `pos` belong to the chunk, or are `AV_NOPTS_VALUE`
+    /// and -1 if unknown, and are set on the packet starting in it.
+    pub fn parse(&mut self, data: &[u8], pts: i64, dts: i64, pos: i64) -> Result<Vec<Packet>> {
+        self.buf.clear();
+        self.buf.extend_from_slice(data);
+        self.buf
+            .resize(data.len() + ffi::AV_INPUT_BUFFER_PADDING_SIZE as usize, 0);
+        let (mut pts, mut dts, mut pos) = (pts, dts, pos);
+        let mut packets = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let remaining = c_int::try_from(data.len() - offset).unwrap_or(c_int::MAX);
+            let (consumed, packet) = self.parse2(offset, remaining, pts, dts, pos)?;
+            // No progress, which would loop forever.
+            if consumed == 0 && packet.is_none() {
+                return Err(Error::INVALID_ARGUMENT);
+            }
+            packets.extend(packet);
+            offset += consumed;
+            (pts, dts, pos) = (ffi::AV_NOPTS_VALUE, ffi::AV_NOPTS_VALUE, -1);
+        }
+        Ok(packets)
+    }
+
+    /// Signal the end of the stream, returning the remaining packets.
+    pub fn flush(&mut self) -> Result<Vec<Packet>> {
+        self.buf.clear();
+        self.buf
+            .resize(ffi::AV_INPUT_BUFFER_PADDING_SIZE as usize, 0);
+        let mut packets = Vec::new();
+        // Each call with an empty input drains at most one packet.
+        while let (_, Some(packet)) =
+            self.parse2(0, 0, ffi::AV_NOPTS_VALUE, ffi::AV_NOPTS_VALUE, -1)?
+        {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn parse2(
+        &mut self,
+        offset: usize,
+        size: c_int,
+        pts: i64,
+        dts: i64,
+        pos: i64,
+    ) -> Result<(usize, Option<Packet>)> {
+        let mut out = ptr::null_mut();
+        let mut out_size = 0;
+        let consumed = unsafe {
+            ffi::av_parser_parse2(
+                self.parser.as_ptr(),
+                self.codec_ctx.as_ptr(),
+                &mut out,
+                &mut out_size,
+                self.buf[offset..].as_ptr(),
+                size,
+                pts,
+                dts,
+                pos,
+            )
+        };
+        // av_parser_parse2() returns the consumed bytes, not an error code.
+        let consumed = usize::try_from(consumed).map_err(|_| Error::from_raw(consumed))?;
+        if out.is_null() || out_size <= 0 {
+            return Ok((consumed, None));
+        }
+        let mut packet =
+            Packet::from_slice(unsafe { slice::from_raw_parts(out, out_size as usize) })?;
+        let parser = unsafe { self.parser.as_ref() };
+        // Only the plain fields are set, the data is left untouched.
+        let raw = unsafe { packet.as_mut_raw() };
+        raw.pts = parser.pts;
+        raw.dts = parser.dts;
+        raw.pos = parser.pos;
+        raw.duration = parser.duration.into();
+        if parser.key_frame == 1 {
+            raw.flags |= ffi::AV_PKT_FLAG_KEY as c_int;
+        }
+        Ok((consumed, Some(packet)))
+    }
+
+    /// Whether the last packet is a key frame, `None` if unknown.
+    pub fn key_frame(&self) -> Option<bool> {
+        match unsafe { self.parser.as_ref() }.key_frame {
+            -1 => None,
+            key_frame => Some(key_frame == 1),
+        }
+    }
+
+    /// Picture type of the last packet, e.g. `AV_PICTURE_TYPE_I`.
+    pub fn pict_type(&self) -> ffi::AVPictureType {
+        unsafe { self.parser.as_ref() }.pict_type as ffi::AVPictureType
+    }
+
+    pub fn width(&self) -> c_int {
+        unsafe { self.parser.as_ref() }.width
+    }
+
+    pub fn height(&self) -> c_int {
+        unsafe { self.parser.as_ref() }.height
+    }
+
+    /// `AVPixelFormat` for video or `AVSampleFormat` for audio, -1 if
+    /// unknown.
+    pub fn format(&self) -> c_int {
+        unsafe { self.parser.as_ref() }.format
+    }
+
+    /// Number of extra half frames to display the last packet for, e.g. for
+    /// soft telecine.
+    pub fn repeat_pict(&self) -> c_int {
+        unsafe { self.parser.as_ref() }.repeat_pict
+    }
+}
+
+impl Drop for Parser {
+    fn drop(&mut self) {
+        let mut codec_ctx = self.codec_ctx.as_ptr();
+        unsafe {
+            ffi::av_parser_close(self.parser.as_ptr());
+            ffi::avcodec_free_context(&mut codec_ctx);
+        }
+    }
+}
+
+impl fmt::Debug for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parser")
+            .field("key_frame", &self.key_frame())
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .field("format", &self.format())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An ADTS header of an AAC LC frame at 44100Hz stereo, `len` bytes
+    /// including the header, and a payload of zeros.
+    fn adts_frame(len: usize) -> Vec<u8> {
+        let mut frame = vec![0; len];
+        frame[..7].copy_from_slice(&[
+            0xff,
+            0xf1,
+            0x50,
+            0x80 | (len >> 11) as u8,
+            (len >> 3) as u8,
+            ((len & 7) << 5) as u8 | 0x1f,
+            0xfc,
+        ]);
+        frame
+    }
+
+    #[test]
+    fn test_adts() {
+        assert!(Parser::new(ffi::AV_CODEC_ID_NONE).is_err());
+        let mut parser = Parser::new(ffi::AV_CODEC_ID_AAC).unwrap();
+        let stream: Vec<u8> = [adts_frame(20), adts_frame(30), adts_frame(25)].concat();
+        let mut packets = Vec::new();
+        for (i, chunk) in stream.chunks(8).enumerate() {
+            packets.extend(parser.parse(chunk, i as i64, i as i64, -1).unwrap());
+        }
+        packets.extend(parser.flush().unwrap());
+        let sizes: Vec<_> = packets.iter().map(|x| x.size).collect();
+        assert_eq!(sizes, [20, 30, 25]);
+        assert!(parser.flush().unwrap().is_empty());
+    }
+}
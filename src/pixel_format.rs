@@ -0,0 +1,219 @@
+//! Pixel formats and their descriptors(`AVPixFmtDescriptor`).
+use crate::error::Error;
+use crate::ffi;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::ptr;
+use std::str::FromStr;
+
+/// An `AVPixelFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelFormat(ffi::AVPixelFormat);
+
+impl PixelFormat {
+    /// `AV_PIX_FMT_NONE`
+    pub const NONE: Self = Self(ffi::AV_PIX_FMT_NONE);
+
+    pub const fn from_raw(pix_fmt: ffi::AVPixelFormat) -> Self {
+        Self(pix_fmt)
+    }
+
+    pub const fn as_raw(self) -> ffi::AVPixelFormat {
+        self.0
+    }
+
+    /// `av_pix_fmt_desc_get`, `None` if the format is unknown.
+    pub fn descriptor(self) -> Option<&'static ffi::AVPixFmtDescriptor> {
+        unsafe { ffi::av_pix_fmt_desc_get(self.0).as_ref() }
+    }
+
+    /// Name of the format, e.g. `"yuv420p"`.
+    pub fn name(self) -> Option<&'static str> {
+        let desc = self.descriptor()?;
+        unsafe { CStr::from_ptr(desc.name) }.to_str().ok()
+    }
+
+    /// Descriptors of the components(e.g. Y, U and V), empty if the format
+    /// is unknown.
+    pub fn components(self) -> &'static [ffi::AVComponentDescriptor] {
+        self.descriptor()
+            .map_or(&[], |desc| &desc.comp[..desc.nb_components as usize])
+    }
+
+    /// Number of planes, 0 if the format is unknown.
+    pub fn planes(self) -> usize {
+        unsafe { ffi::av_pix_fmt_count_planes(self.0) }.max(0) as usize
+    }
+
+    /// Bits per pixel excluding padding(`av_get_bits_per_pixel`), e.g. 12
+    /// for `yuv420p`, 0 if unknown.
+    pub fn bits_per_pixel(self) -> u32 {
+        self.descriptor()
+            .map_or(0, |desc| unsafe { ffi::av_get_bits_per_pixel(desc) as u32 })
+    }
+
+    /// Bits per pixel including padding(`av_get_padded_bits_per_pixel`).
+    pub fn padded_bits_per_pixel(self) -> u32 {
+        self.descriptor().map_or(0, |desc| unsafe {
+            ffi::av_get_padded_bits_per_pixel(desc) as u32
+        })
+    }
+
+    /// Log2 of the horizontal and vertical chroma subsampling, e.g. `(1, 1)`
+    /// for `yuv420p`.
+    pub fn chroma_shift(self) -> (u32, u32) {
+        self.descriptor().map_or((0, 0), |desc| {
+            (desc.log2_chroma_w.into(), desc.log2_chroma_h.into())
+        })
+    }
+
+    fn has_flag(self, flag: u32) -> bool {
+        self.descriptor()
+            .is_some_and(|desc| desc.flags & u64::from(flag) != 0)
+    }
+
+    /// `AV_PIX_FMT_FLAG_PLANAR`
+    pub fn is_planar(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_PLANAR)
+    }
+
+    /// `AV_PIX_FMT_FLAG_RGB`
+    pub fn is_rgb(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_RGB)
+    }
+
+    /// `AV_PIX_FMT_FLAG_ALPHA`
+    pub fn has_alpha(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_ALPHA)
+    }
+
+    /// `AV_PIX_FMT_FLAG_HWACCEL`, e.g. `vaapi`, `cuda`.
+    pub fn is_hw(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_HWACCEL)
+    }
+
+    /// `AV_PIX_FMT_FLAG_BAYER`
+    pub fn is_bayer(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_BAYER)
+    }
+
+    /// `AV_PIX_FMT_FLAG_FLOAT`
+    pub fn is_float(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_FLOAT)
+    }
+
+    /// `AV_PIX_FMT_FLAG_BE`
+    pub fn is_big_endian(self) -> bool {
+        self.has_flag(ffi::AV_PIX_FMT_FLAG_BE)
+    }
+
+    /// The format with the other byte order, e.g. `gray16le` for `gray16be`,
+    /// `None` if there is no such format(`av_pix_fmt_swap_endianness`).
+    pub fn swap_endianness(self) -> Option<Self> {
+        let swapped = Self(unsafe { ffi::av_pix_fmt_swap_endianness(self.0) });
+        (swapped != Self::NONE).then_some(swapped)
+    }
+
+    /// The format with the byte order of the target, which is what the
+    /// `AV_PIX_FMT_NE` aliases(e.g. `AV_PIX_FMT_GRAY16`) resolve to. Formats
+    /// without byte order variants are returned as is.
+    pub fn native_endian(self) -> Self {
+        if self.is_big_endian() == cfg!(target_endian = "big") {
+            self
+        } else {
+            self.swap_endianness().unwrap_or(self)
+        }
+    }
+
+    /// The candidate losing the least information when converted from this
+    /// format(`av_find_best_pix_fmt_of_2`), `None` if there is no
+    /// candidate.
+    pub fn best_match(self, candidates: &[PixelFormat]) -> Option<Self> {
+        let has_alpha = self.has_alpha().into();
+        let best = candidates.iter().fold(Self::NONE, |best, candidate| {
+            Self(unsafe {
+                ffi::av_find_best_pix_fmt_of_2(
+                    best.0,
+                    candidate.0,
+                    self.0,
+                    has_alpha,
+                    ptr::null_mut(),
+                )
+            })
+        });
+        (best != Self::NONE).then_some(best)
+    }
+}
+
+impl From<ffi::AVPixelFormat> for PixelFormat {
+    fn from(pix_fmt: ffi::AVPixelFormat) -> Self {
+        Self(pix_fmt)
+    }
+}
+
+impl From<PixelFormat> for ffi::AVPixelFormat {
+    fn from(pix_fmt: PixelFormat) -> Self {
+        pix_fmt.0
+    }
+}
+
+/// Find a format by its name(`av_get_pix_fmt`).
+impl FromStr for PixelFormat {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        let name = CString::new(name).map_err(|_| Error::INVALID_ARGUMENT)?;
+        let pix_fmt = Self(unsafe { ffi::av_get_pix_fmt(name.as_ptr()) });
+        if pix_fmt == Self::NONE {
+            Err(Error::INVALID_ARGUMENT)
+        } else {
+            Ok(pix_fmt)
+        }
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name().unwrap_or("none"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_yuv420p() {
+        let pix_fmt: PixelFormat = "yuv420p".parse().unwrap();
+        assert_eq!(pix_fmt, PixelFormat::from(ffi::AV_PIX_FMT_YUV420P));
+        assert_eq!(pix_fmt.to_string(), "yuv420p");
+        assert_eq!(pix_fmt.components().len(), 3);
+        assert_eq!(pix_fmt.planes(), 3);
+        assert_eq!(pix_fmt.bits_per_pixel(), 12);
+        assert_eq!(pix_fmt.chroma_shift(), (1, 1));
+        assert!(pix_fmt.is_planar());
+        assert!(!pix_fmt.is_rgb() && !pix_fmt.has_alpha() && !pix_fmt.is_hw());
+        assert!("no_such_format".parse::<PixelFormat>().is_err());
+    }
+
+    #[test]
+    fn test_endianness() {
+        let gray16be = PixelFormat::from(ffi::AV_PIX_FMT_GRAY16BE);
+        let gray16le = PixelFormat::from(ffi::AV_PIX_FMT_GRAY16LE);
+        assert!(gray16be.is_big_endian());
+        assert_eq!(gray16be.swap_endianness(), Some(gray16le));
+        assert_eq!(gray16be.native_endian(), ffi::AV_PIX_FMT_GRAY16.into());
+        assert_eq!(gray16le.native_endian(), ffi::AV_PIX_FMT_GRAY16.into());
+        let rgb24 = PixelFormat::from(ffi::AV_PIX_FMT_RGB24);
+        assert_eq!(rgb24.swap_endianness(), None);
+        assert_eq!(rgb24.native_endian(), rgb24);
+    }
+
+    #[test]
+    fn test_best_match() {
+        let rgba = PixelFormat::from(ffi::AV_PIX_FMT_RGBA);
+        let yuv420p = PixelFormat::from(ffi::AV_PIX_FMT_YUV420P);
+        assert_eq!(rgba.best_match(&[yuv420p, rgba]), Some(rgba));
+        assert_eq!(rgba.best_match(&[]), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Detect the format of content without opening a demuxer
+//! (`av_probe_input_format3`).
+use crate::ffi;
+use crate::registry::InputFormat;
+use std::ffi::{c_int, CString};
+use std::io::{self, Read};
+use std::ptr;
+
+/// `AVPROBE_SCORE_RETRY`, a macro bindgen doesn't generate.
+const SCORE_RETRY: c_int = ffi::AVPROBE_SCORE_MAX as c_int / 4;
+/// Initial and maximum probe window of `av_probe_input_buffer2`.
+const PROBE_BUF_MIN: usize = 2048;
+const PROBE_BUF_MAX: usize = 1 << 20;
+
+/// Confidence of a probe result by the `AVPROBE_SCORE_*` thresholds, carrying
+/// the score, ordered from the least to the most confident.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProbeScore {
+    /// Below `AVPROBE_SCORE_RETRY`, a guess.
+    Low(c_int),
+    /// `AVPROBE_SCORE_RETRY` or above, probing more data may give a better
+    /// result.
+    Retry(c_int),
+    /// `AVPROBE_SCORE_EXTENSION` or above, as confident as matching the file
+    /// extension.
+    Extension(c_int),
+    /// `AVPROBE_SCORE_MAX`
+    Max,
+}
+
+impl ProbeScore {
+    pub fn from_raw(score: c_int) -> Self {
+        if score >= ffi::AVPROBE_SCORE_MAX as c_int {
+            Self::Max
+        } else if score >= ffi::AVPROBE_SCORE_EXTENSION as c_int {
+            Self::Extension(score)
+        } else if score >= SCORE_RETRY {
+            Self::Retry(score)
+        } else {
+            Self::Low(score)
+        }
+    }
+
+    /// The score, 1 to `AVPROBE_SCORE_MAX`.
+    pub fn value(self) -> c_int {
+        match self {
+            Self::Low(score) | Self::Retry(score) | Self::Extension(score) => score,
+            Self::Max => ffi::AVPROBE_SCORE_MAX as c_int,
+        }
+    }
+}
+
+/// Result of [`probe_reader`].
+#[derive(Debug)]
+pub struct ProbeResult {
+    /// The detected format, `None` if no demuxer recognizes the content.
+    pub format: Option<(InputFormat, ProbeScore)>,
+    /// The bytes consumed from the reader, to be prepended to the rest of
+    /// the input.
+    pub consumed: Vec<u8>,
+}
+
+/// Probe `buf`, which is followed by `AVPROBE_PADDING_SIZE` zeroed bytes.
+fn probe_padded(
+    buf: &mut [u8],
+    filename: &CString,
+    threshold: c_int,
+) -> Option<(InputFormat, ProbeScore)> {
+    let buf_size = buf.len() - ffi::AVPROBE_PADDING_SIZE as usize;
+    let mut pd = ffi::AVProbeData {
+        filename: filename.as_ptr(),
+        buf: buf.as_mut_ptr(),
+        buf_size: c_int::try_from(buf_size).ok()?,
+        mime_type: ptr::null(),
+    };
+    let mut score = 0;
+    let format =
+        unsafe { ffi::av_probe_input_format3(ptr::addr_of_mut!(pd), 1, &mut score).as_ref() }?;
+    (score > threshold).then(|| (InputFormat::from_raw(format), ProbeScore::from_raw(score)))
+}
+
+/// Detect the format of the beginning of some content, using the extension
+/// of `filename_hint` too if given. `None` if no demuxer recognizes it.
+pub fn probe_bytes(data: &[u8], filename_hint: Option<&str>) -> Option<(InputFormat, ProbeScore)> {
+    let filename = CString::new(filename_hint.unwrap_or_default()).ok()?;
+    let mut buf = Vec::with_capacity(data.len() + ffi::AVPROBE_PADDING_SIZE as usize);
+    buf.extend_from_slice(data);
+    buf.resize(data.len() + ffi::AVPROBE_PADDING_SIZE as usize, 0);
+    probe_padded(&mut buf, &filename, 0)
+}
+
+/// Detect the format of content read from `reader`, doubling the probe
+/// window from 2 KiB to 1 MiB until the score is above
+/// `AVPROBE_SCORE_RETRY`, as `av_probe_input_buffer2` does. Below that, the
+/// result of the whole window or the whole input if it ends is returned.
+///
+/// The probed bytes are consumed from `reader` and returned along with the
+/// result, so they can be prepended to the rest of the input.
+pub fn probe_reader<R: Read>(
+    mut reader: R,
+    filename_hint: Option<&str>,
+) -> io::Result<ProbeResult> {
+    let filename = CString::new(filename_hint.unwrap_or_default())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let mut data = Vec::new();
+    let mut probe_size = PROBE_BUF_MIN;
+    loop {
+        let len = data.len();
+        (&mut reader)
+            .take((probe_size - len) as u64)
+            .read_to_end(&mut data)?;
+        let len = data.len();
+        let last = len < probe_size || probe_size >= PROBE_BUF_MAX;
+        let threshold = if last { 0 } else { SCORE_RETRY };
+
+        data.resize(len + ffi::AVPROBE_PADDING_SIZE as usize, 0);
+        let format = probe_padded(&mut data, &filename, threshold);
+        data.truncate(len);
+        if format.is_some() || last {
+            return Ok(ProbeResult {
+                format,
+                consumed: data,
+            });
+        }
+        probe_size = (probe_size * 2).min(PROBE_BUF_MAX);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A WAV header of 16 bit mono PCM at 8000Hz.
+    const WAV_HEADER: &[u8] = b"RIFF\x24\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0\
+        \x40\x1f\0\0\x80\x3e\0\0\x02\0\x10\0data\0\0\0\0";
+
+    #[test]
+    fn test_probe() {
+        let (format, score) = probe_bytes(WAV_HEADER, None).unwrap();
+        assert_eq!(format.name(), "wav");
+        assert!(score >= ProbeScore::Extension(ffi::AVPROBE_SCORE_EXTENSION as c_int));
+
+        let input = [WAV_HEADER, &[0; 5000]].concat();
+        let mut reader = io::Cursor::new(&input);
+        let probed = probe_reader(&mut reader, Some("upload.wav")).unwrap();
+        assert_eq!(probed.format.unwrap().0.name(), "wav");
+        assert_eq!(reader.position(), PROBE_BUF_MIN as u64);
+        assert_eq!(probed.consumed, input[..PROBE_BUF_MIN]);
+
+        assert_eq!(ProbeScore::from_raw(25), ProbeScore::Retry(25));
+        assert_eq!(ProbeScore::from_raw(100).value(), 100);
+    }
+}
@@ -0,0 +1,479 @@
+//! Iterate the codecs, formats, filters, bitstream filters, protocols and
+//! devices the linked FFmpeg is built with, e.g. for a capabilities report.
+use crate::ffi;
+use std::ffi::{c_char, c_int, CStr};
+use std::fmt;
+use std::ptr;
+use std::sync::Once;
+
+fn to_str(s: *const c_char) -> Option<&'static str> {
+    if s.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(s) }.to_str().ok()
+    }
+}
+
+/// Items of a comma separated list, e.g. `"mp4,m4a,mov"`.
+fn split_list(s: *const c_char) -> impl Iterator<Item = &'static str> {
+    to_str(s)
+        .into_iter()
+        .flat_map(|s| s.split(','))
+        .filter(|s| !s.is_empty())
+}
+
+/// `AV_PROFILE_UNKNOWN`(`FF_PROFILE_UNKNOWN` before FFmpeg 6.1), which
+/// terminates the profile lists.
+const PROFILE_UNKNOWN: c_int = -99;
+
+/// A profile of a codec, e.g. `"High"` of H.264.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    pub id: c_int,
+    pub name: &'static str,
+}
+
+fn profiles(
+    codec_id: ffi::AVCodecID,
+    profiles: *const ffi::AVProfile,
+) -> impl Iterator<Item = Profile> {
+    (0..)
+        .map_while(move |i| {
+            if profiles.is_null() {
+                return None;
+            }
+            let profile = unsafe { &*profiles.add(i) };
+            (profile.profile != PROFILE_UNKNOWN).then_some(profile.profile)
+        })
+        .filter_map(move |id| {
+            let name = to_str(unsafe { ffi::avcodec_profile_name(codec_id, id) })?;
+            Some(Profile { id, name })
+        })
+}
+
+/// A decoder or encoder(`AVCodec`).
+#[derive(Clone, Copy)]
+pub struct Codec(&'static ffi::AVCodec);
+
+impl Codec {
+    pub fn as_raw(self) -> &'static ffi::AVCodec {
+        self.0
+    }
+
+    /// Short name, e.g. `"h264_cuvid"`.
+    pub fn name(self) -> &'static str {
+        to_str(self.0.name).unwrap_or_default()
+    }
+
+    pub fn long_name(self) -> Option<&'static str> {
+        to_str(self.0.long_name)
+    }
+
+    pub fn media_type(self) -> ffi::AVMediaType {
+        self.0.type_
+    }
+
+    pub fn id(self) -> ffi::AVCodecID {
+        self.0.id
+    }
+
+    pub fn is_decoder(self) -> bool {
+        unsafe { ffi::av_codec_is_decoder(self.0) != 0 }
+    }
+
+    pub fn is_encoder(self) -> bool {
+        unsafe { ffi::av_codec_is_encoder(self.0) != 0 }
+    }
+
+    /// `AV_CODEC_CAP_*` flags.
+    pub fn capabilities(self) -> c_int {
+        self.0.capabilities
+    }
+
+    /// Check an `AV_CODEC_CAP_*` flag, e.g. `AV_CODEC_CAP_HARDWARE`.
+    pub fn has_capability(self, cap: u32) -> bool {
+        self.0.capabilities as u32 & cap != 0
+    }
+
+    /// Profiles the codec supports, named by `avcodec_profile_name`.
+    pub fn profiles(self) -> impl Iterator<Item = Profile> {
+        profiles(self.0.id, self.0.profiles)
+    }
+
+    /// Properties of the codec id(`avcodec_descriptor_get`).
+    pub fn descriptor(self) -> Option<CodecDescriptor> {
+        CodecDescriptor::get(self.0.id)
+    }
+}
+
+impl fmt::Debug for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Codec")
+            .field("name", &self.name())
+            .field("media_type", &self.media_type())
+            .field("is_decoder", &self.is_decoder())
+            .finish()
+    }
+}
+
+/// Iterate the decoders and encoders(`av_codec_iterate`).
+pub fn codecs() -> impl Iterator<Item = Codec> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe { ffi::av_codec_iterate(&mut opaque).as_ref() }).map(Codec)
+}
+
+/// Properties of a codec id shared by its decoders and encoders
+/// (`AVCodecDescriptor`).
+#[derive(Clone, Copy)]
+pub struct CodecDescriptor(&'static ffi::AVCodecDescriptor);
+
+impl CodecDescriptor {
+    /// `avcodec_descriptor_get`, `None` if the id is unknown.
+    pub fn get(id: ffi::AVCodecID) -> Option<Self> {
+        unsafe { ffi::avcodec_descriptor_get(id).as_ref() }.map(Self)
+    }
+
+    pub fn as_raw(self) -> &'static ffi::AVCodecDescriptor {
+        self.0
+    }
+
+    /// Name of the codec id, e.g. `"h264"`.
+    pub fn name(self) -> &'static str {
+        to_str(self.0.name).unwrap_or_default()
+    }
+
+    pub fn long_name(self) -> Option<&'static str> {
+        to_str(self.0.long_name)
+    }
+
+    pub fn media_type(self) -> ffi::AVMediaType {
+        self.0.type_
+    }
+
+    /// `AV_CODEC_PROP_*` flags.
+    pub fn props(self) -> c_int {
+        self.0.props
+    }
+
+    /// `AV_CODEC_PROP_INTRA_ONLY`
+    pub fn is_intra_only(self) -> bool {
+        self.0.props as u32 & ffi::AV_CODEC_PROP_INTRA_ONLY != 0
+    }
+
+    /// `AV_CODEC_PROP_LOSSY`
+    pub fn is_lossy(self) -> bool {
+        self.0.props as u32 & ffi::AV_CODEC_PROP_LOSSY != 0
+    }
+
+    /// `AV_CODEC_PROP_LOSSLESS`
+    pub fn is_lossless(self) -> bool {
+        self.0.props as u32 & ffi::AV_CODEC_PROP_LOSSLESS != 0
+    }
+
+    /// MIME types, e.g. `"image/png"`.
+    pub fn mime_types(self) -> impl Iterator<Item = &'static str> {
+        let mime_types = self.0.mime_types;
+        (0..).map_while(move |i| {
+            if mime_types.is_null() {
+                return None;
+            }
+            to_str(unsafe { *mime_types.add(i) })
+        })
+    }
+
+    pub fn profiles(self) -> impl Iterator<Item = Profile> {
+        profiles(self.0.id, self.0.profiles)
+    }
+}
+
+impl fmt::Debug for CodecDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CodecDescriptor")
+            .field("name", &self.name())
+            .field("media_type", &self.media_type())
+            .field("props", &self.props())
+            .finish()
+    }
+}
+
+/// A demuxer or input device(`AVInputFormat`).
+#[derive(Clone, Copy)]
+pub struct InputFormat(&'static ffi::AVInputFormat);
+
+impl InputFormat {
+    pub fn from_raw(format: &'static ffi::AVInputFormat) -> Self {
+        Self(format)
+    }
+
+    pub fn as_raw(self) -> &'static ffi::AVInputFormat {
+        self.0
+    }
+
+    /// Short name, e.g. `"mov,mp4,m4a,3gp,3g2,mj2"`.
+    pub fn name(self) -> &'static str {
+        to_str(self.0.name).unwrap_or_default()
+    }
+
+    pub fn long_name(self) -> Option<&'static str> {
+        to_str(self.0.long_name)
+    }
+
+    /// `AVFMT_*` flags.
+    pub fn flags(self) -> c_int {
+        self.0.flags
+    }
+
+    pub fn mime_types(self) -> impl Iterator<Item = &'static str> {
+        split_list(self.0.mime_type)
+    }
+
+    /// File extensions without the dot, e.g. `"mkv"`.
+    pub fn extensions(self) -> impl Iterator<Item = &'static str> {
+        split_list(self.0.extensions)
+    }
+}
+
+impl fmt::Debug for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputFormat")
+            .field("name", &self.name())
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
+
+/// A muxer or output device(`AVOutputFormat`).
+#[derive(Clone, Copy)]
+pub struct OutputFormat(&'static ffi::AVOutputFormat);
+
+impl OutputFormat {
+    pub fn as_raw(self) -> &'static ffi::AVOutputFormat {
+        self.0
+    }
+
+    /// Short name, e.g. `"mp4"`.
+    pub fn name(self) -> &'static str {
+        to_str(self.0.name).unwrap_or_default()
+    }
+
+    pub fn long_name(self) -> Option<&'static str> {
+        to_str(self.0.long_name)
+    }
+
+    /// `AVFMT_*` flags.
+    pub fn flags(self) -> c_int {
+        self.0.flags
+    }
+
+    pub fn mime_types(self) -> impl Iterator<Item = &'static str> {
+        split_list(self.0.mime_type)
+    }
+
+    /// File extensions without the dot, e.g. `"mp4"`.
+    pub fn extensions(self) -> impl Iterator<Item = &'static str> {
+        split_list(self.0.extensions)
+    }
+
+    pub fn default_audio_codec(self) -> ffi::AVCodecID {
+        self.0.audio_codec
+    }
+
+    pub fn default_video_codec(self) -> ffi::AVCodecID {
+        self.0.video_codec
+    }
+
+    pub fn default_subtitle_codec(self) -> ffi::AVCodecID {
+        self.0.subtitle_codec
+    }
+}
+
+impl fmt::Debug for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutputFormat")
+            .field("name", &self.name())
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
+
+/// Iterate the demuxers(`av_demuxer_iterate`).
+pub fn demuxers() -> impl Iterator<Item = InputFormat> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe { ffi::av_demuxer_iterate(&mut opaque).as_ref() })
+        .map(InputFormat)
+}
+
+/// Iterate the muxers(`av_muxer_iterate`).
+pub fn muxers() -> impl Iterator<Item = OutputFormat> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe { ffi::av_muxer_iterate(&mut opaque).as_ref() })
+        .map(OutputFormat)
+}
+
+fn register_devices() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe { ffi::avdevice_register_all() });
+}
+
+/// Iterate `av_*_device_next`, which takes and returns mutable pointers before
+/// FFmpeg 5. Fused, since `next(null)` after the end restarts from the first
+/// device.
+fn devices<T: 'static>(next: fn(*const T) -> *const T) -> impl Iterator<Item = &'static T> {
+    register_devices();
+    let mut prev = ptr::null();
+    std::iter::from_fn(move || {
+        prev = next(prev);
+        unsafe { prev.as_ref() }
+    })
+    .fuse()
+}
+
+/// Iterate the audio and video input devices, e.g. `"v4l2"`
+/// (`av_input_*_device_next`).
+pub fn input_devices() -> impl Iterator<Item = InputFormat> {
+    let audio = devices(|prev| unsafe { ffi::av_input_audio_device_next(prev as _) as _ });
+    let video = devices(|prev| unsafe { ffi::av_input_video_device_next(prev as _) as _ });
+    audio.chain(video).map(InputFormat)
+}
+
+/// Iterate the audio and video output devices(`av_output_*_device_next`).
+pub fn output_devices() -> impl Iterator<Item = OutputFormat> {
+    let audio = devices(|prev| unsafe { ffi::av_output_audio_device_next(prev as _) as _ });
+    let video = devices(|prev| unsafe { ffi::av_output_video_device_next(prev as _) as _ });
+    audio.chain(video).map(OutputFormat)
+}
+
+/// A filter of libavfilter(`AVFilter`).
+#[derive(Clone, Copy)]
+pub struct Filter(&'static ffi::AVFilter);
+
+impl Filter {
+    pub fn as_raw(self) -> &'static ffi::AVFilter {
+        self.0
+    }
+
+    /// Name, e.g. `"scale"`.
+    pub fn name(self) -> &'static str {
+        to_str(self.0.name).unwrap_or_default()
+    }
+
+    pub fn description(self) -> Option<&'static str> {
+        to_str(self.0.description)
+    }
+
+    /// `AVFILTER_FLAG_*` flags.
+    pub fn flags(self) -> c_int {
+        self.0.flags
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Filter")
+            .field("name", &self.name())
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
+
+/// Iterate the filters(`av_filter_iterate`).
+pub fn filters() -> impl Iterator<Item = Filter> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe { ffi::av_filter_iterate(&mut opaque).as_ref() }).map(Filter)
+}
+
+/// A bitstream filter definition(`AVBitStreamFilter`), which is
+/// instantiated by [`crate::bitstream_filter::BitstreamFilter`].
+#[derive(Clone, Copy)]
+pub struct BitstreamFilterInfo(&'static ffi::AVBitStreamFilter);
+
+impl BitstreamFilterInfo {
+    pub fn as_raw(self) -> &'static ffi::AVBitStreamFilter {
+        self.0
+    }
+
+    /// Name, e.g. `"h264_mp4toannexb"`.
+    pub fn name(self) -> &'static str {
+        to_str(self.0.name).unwrap_or_default()
+    }
+
+    /// The codec ids the filter accepts, empty if it accepts any.
+    pub fn codec_ids(self) -> impl Iterator<Item = ffi::AVCodecID> {
+        let codec_ids = self.0.codec_ids;
+        (0..).map_while(move |i| {
+            if codec_ids.is_null() {
+                return None;
+            }
+            let codec_id = unsafe { *codec_ids.add(i) };
+            (codec_id != ffi::AV_CODEC_ID_NONE).then_some(codec_id)
+        })
+    }
+}
+
+impl fmt::Debug for BitstreamFilterInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitstreamFilterInfo")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// Iterate the bitstream filters(`av_bsf_iterate`).
+pub fn bitstream_filters() -> impl Iterator<Item = BitstreamFilterInfo> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe { ffi::av_bsf_iterate(&mut opaque).as_ref() })
+        .map(BitstreamFilterInfo)
+}
+
+/// Iterate the names of the input or output protocols, e.g. `"https"`
+/// (`avio_enum_protocols`). Fused, since `avio_enum_protocols` resets the
+/// opaque at the end and restarts from the first protocol.
+pub fn protocols(output: bool) -> impl Iterator<Item = &'static str> {
+    let mut opaque = ptr::null_mut();
+    std::iter::from_fn(move || unsafe {
+        to_str(ffi::avio_enum_protocols(&mut opaque, output.into()))
+    })
+    .fuse()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let rawvideo = codecs()
+            .find(|codec| codec.name() == "rawvideo" && codec.is_decoder())
+            .unwrap();
+        assert_eq!(rawvideo.media_type(), ffi::AVMEDIA_TYPE_VIDEO);
+        assert!(rawvideo.descriptor().unwrap().is_intra_only());
+
+        let png = CodecDescriptor::get(ffi::AV_CODEC_ID_PNG).unwrap();
+        assert!(png.is_lossless());
+        assert!(png.mime_types().any(|mime_type| mime_type == "image/png"));
+        let h264 = CodecDescriptor::get(ffi::AV_CODEC_ID_H264).unwrap();
+        assert!(h264.profiles().any(|profile| profile.name == "High"));
+
+        let wav = muxers().find(|format| format.name() == "wav").unwrap();
+        assert!(wav.extensions().any(|extension| extension == "wav"));
+        assert!(demuxers().any(|format| format.name() == "wav"));
+        assert!(filters().any(|filter| filter.name() == "null"));
+        let null = bitstream_filters().find(|bsf| bsf.name() == "null");
+        assert_eq!(null.unwrap().codec_ids().count(), 0);
+        for output in [false, true] {
+            let mut protocols = protocols(output);
+            assert!(protocols.by_ref().any(|protocol| protocol == "file"));
+            protocols.by_ref().for_each(drop);
+            assert!(protocols.next().is_none());
+        }
+
+        let mut devices = input_devices();
+        let names: Vec<_> = devices.by_ref().map(|device| device.name()).collect();
+        assert!(names.iter().all(|name| !name.is_empty()));
+        assert!(devices.next().is_none());
+        let mut devices = output_devices();
+        let names: Vec<_> = devices.by_ref().map(|device| device.name()).collect();
+        assert!(names.iter().all(|name| !name.is_empty()));
+        assert!(devices.next().is_none());
+    }
+}
@@ -0,0 +1,427 @@
+//! Audio sample formats and an owned buffer of audio samples.
+use crate::compat::ChannelLayoutExt;
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use crate::frame::Frame;
+use std::ffi::{c_int, CStr, CString};
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::str::FromStr;
+
+/// An `AVSampleFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SampleFormat(ffi::AVSampleFormat);
+
+impl SampleFormat {
+    /// `AV_SAMPLE_FMT_NONE`
+    pub const NONE: Self = Self(ffi::AV_SAMPLE_FMT_NONE);
+
+    pub const fn from_raw(sample_fmt: ffi::AVSampleFormat) -> Self {
+        Self(sample_fmt)
+    }
+
+    pub const fn as_raw(self) -> ffi::AVSampleFormat {
+        self.0
+    }
+
+    /// From `AVFrame.format` or `AVCodecParameters.format`.
+    pub(crate) fn from_c_int(format: c_int) -> Self {
+        #[cfg(feature = "typed_enums")]
+        return Self(ffi::AVSampleFormat(format));
+        #[cfg(not(feature = "typed_enums"))]
+        return Self(format);
+    }
+
+    pub(crate) fn to_c_int(self) -> c_int {
+        #[cfg(feature = "typed_enums")]
+        return self.0 .0;
+        #[cfg(not(feature = "typed_enums"))]
+        return self.0;
+    }
+
+    /// Name of the format, e.g. `"fltp"` (`av_get_sample_fmt_name`).
+    pub fn name(self) -> Option<&'static str> {
+        let name = unsafe { ffi::av_get_sample_fmt_name(self.0) };
+        if name.is_null() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(name) }.to_str().ok()
+        }
+    }
+
+    /// `av_get_bytes_per_sample`, 0 if the format is unknown.
+    pub fn bytes_per_sample(self) -> usize {
+        unsafe { ffi::av_get_bytes_per_sample(self.0) }.max(0) as usize
+    }
+
+    /// `av_sample_fmt_is_planar`
+    pub fn is_planar(self) -> bool {
+        unsafe { ffi::av_sample_fmt_is_planar(self.0) != 0 }
+    }
+
+    /// The interleaved variant, e.g. `flt` for `fltp`
+    /// (`av_get_packed_sample_fmt`).
+    pub fn packed(self) -> Self {
+        Self(unsafe { ffi::av_get_packed_sample_fmt(self.0) })
+    }
+
+    /// The planar variant, e.g. `fltp` for `flt`
+    /// (`av_get_planar_sample_fmt`).
+    pub fn planar(self) -> Self {
+        Self(unsafe { ffi::av_get_planar_sample_fmt(self.0) })
+    }
+
+    /// Size of `samples` samples of `channels` channels without padding
+    /// (`av_samples_get_buffer_size`).
+    pub fn buffer_size(self, channels: usize, samples: usize) -> Result<usize> {
+        let size = check(unsafe {
+            ffi::av_samples_get_buffer_size(
+                ptr::null_mut(),
+                to_c_int(channels)?,
+                to_c_int(samples)?,
+                self.0,
+                1,
+            )
+        })?;
+        Ok(size as usize)
+    }
+}
+
+impl From<ffi::AVSampleFormat> for SampleFormat {
+    fn from(sample_fmt: ffi::AVSampleFormat) -> Self {
+        Self(sample_fmt)
+    }
+}
+
+impl From<SampleFormat> for ffi::AVSampleFormat {
+    fn from(sample_fmt: SampleFormat) -> Self {
+        sample_fmt.0
+    }
+}
+
+/// Find a format by its name(`av_get_sample_fmt`).
+impl FromStr for SampleFormat {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        let name = CString::new(name).map_err(|_| Error::INVALID_ARGUMENT)?;
+        let sample_fmt = Self(unsafe { ffi::av_get_sample_fmt(name.as_ptr()) });
+        if sample_fmt == Self::NONE {
+            Err(Error::INVALID_ARGUMENT)
+        } else {
+            Ok(sample_fmt)
+        }
+    }
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name().unwrap_or("none"))
+    }
+}
+
+/// Format, number of channels and number of samples of an audio frame,
+/// checking that every plane is within the buffers of the frame, so the
+/// samples can be read through `extended_data`.
+pub(crate) fn check_audio_frame(frame: &Frame) -> Result<(SampleFormat, usize, usize)> {
+    let format = SampleFormat::from_c_int(frame.format);
+    let channels = usize::try_from(frame.nb_channels()).map_err(|_| Error::INVALID_ARGUMENT)?;
+    let samples = usize::try_from(frame.nb_samples).map_err(|_| Error::INVALID_ARGUMENT)?;
+    if format.bytes_per_sample() == 0 || channels == 0 || frame.extended_data.is_null() {
+        return Err(Error::INVALID_ARGUMENT);
+    }
+    let (planes, plane_size) = if format.is_planar() {
+        (channels, samples.checked_mul(format.bytes_per_sample()))
+    } else {
+        let sample_size = channels.checked_mul(format.bytes_per_sample());
+        (1, sample_size.and_then(|x| x.checked_mul(samples)))
+    };
+    let plane_size = plane_size.ok_or(Error::INVALID_ARGUMENT)?;
+    let extended_buf: &[*mut ffi::AVBufferRef] = if frame.extended_buf.is_null() {
+        &[]
+    } else {
+        let len = frame.nb_extended_buf.max(0) as usize;
+        unsafe { slice::from_raw_parts(frame.extended_buf, len) }
+    };
+    let buffers: Vec<_> = frame
+        .buf
+        .iter()
+        .chain(extended_buf)
+        .filter_map(|buf| unsafe { buf.as_ref() })
+        .map(|buf| (buf.data as usize, buf.size as _))
+        .collect();
+    let planes = unsafe { slice::from_raw_parts(frame.extended_data, planes) };
+    let in_buffers = |plane: &*mut u8| {
+        let start = *plane as usize;
+        !plane.is_null()
+            && buffers.iter().any(|&(data, size)| {
+                start >= data
+                    && start
+                        .checked_add(plane_size)
+                        .is_some_and(|end| end <= data.saturating_add(size))
+            })
+    };
+    if planes.iter().all(in_buffers) {
+        Ok((format, channels, samples))
+    } else {
+        Err(Error::INVALID_ARGUMENT)
+    }
+}
+
+fn to_c_int(value: usize) -> Result<c_int> {
+    c_int::try_from(value).map_err(|_| Error::INVALID_ARGUMENT)
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Rust type of the samples of a format, ignoring whether it's planar.
+pub trait Sample: private::Sealed + Copy {
+    /// The interleaved format, e.g. `AV_SAMPLE_FMT_FLT` for `f32`.
+    const FORMAT: SampleFormat;
+}
+
+macro_rules! sample {
+    ($($ty:ty => $format:ident),*) => {$(
+        impl private::Sealed for $ty {}
+
+        impl Sample for $ty {
+            const FORMAT: SampleFormat = SampleFormat(ffi::$format);
+        }
+    )*};
+}
+
+sample!(
+    u8 => AV_SAMPLE_FMT_U8,
+    i16 => AV_SAMPLE_FMT_S16,
+    i32 => AV_SAMPLE_FMT_S32,
+    i64 => AV_SAMPLE_FMT_S64,
+    f32 => AV_SAMPLE_FMT_FLT,
+    f64 => AV_SAMPLE_FMT_DBL
+);
+
+/// Owned audio samples allocated by `av_samples_alloc`, either planar(a
+/// plane per channel) or interleaved(a single plane).
+pub struct SampleBuffer {
+    /// Pointers to the planes, all in the allocation of the first one.
+    data: Vec<*mut u8>,
+    format: SampleFormat,
+    channels: usize,
+    samples: usize,
+}
+
+unsafe impl Send for SampleBuffer {}
+unsafe impl Sync for SampleBuffer {}
+
+impl SampleBuffer {
+    /// Allocate `samples` samples of `channels` channels filled with silence.
+    pub fn new(format: SampleFormat, channels: usize, samples: usize) -> Result<Self> {
+        if channels == 0 || samples == 0 || format.bytes_per_sample() == 0 {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        let planes = if format.is_planar() { channels } else { 1 };
+        let mut data = vec![ptr::null_mut(); planes];
+        let mut linesize = 0;
+        check(unsafe {
+            ffi::av_samples_alloc(
+                data.as_mut_ptr(),
+                &mut linesize,
+                to_c_int(channels)?,
+                to_c_int(samples)?,
+                format.0,
+                0,
+            )
+        })?;
+        let mut buffer = Self {
+            data,
+            format,
+            channels,
+            samples,
+        };
+        buffer.set_silence()?;
+        Ok(buffer)
+    }
+
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Number of samples per channel.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Number of planes, `channels()` if planar, otherwise 1.
+    pub fn planes(&self) -> usize {
+        self.data.len()
+    }
+
+    fn plane_size(&self) -> usize {
+        let channels = if self.format.is_planar() {
+            1
+        } else {
+            self.channels
+        };
+        self.samples * channels * self.format.bytes_per_sample()
+    }
+
+    /// Bytes of a plane, without the alignment padding.
+    pub fn plane_bytes(&self, index: usize) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data[index], self.plane_size()) }
+    }
+
+    pub fn plane_bytes_mut(&mut self, index: usize) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.data[index], self.plane_size()) }
+    }
+
+    /// Samples of a plane, which is a channel if planar, otherwise the
+    /// interleaved channels. `None` if `T` doesn't match the format, e.g.
+    /// `f32` for `AV_SAMPLE_FMT_S16`.
+    pub fn plane<T: Sample>(&self, index: usize) -> Option<&[T]> {
+        if T::FORMAT != self.format.packed() {
+            return None;
+        }
+        let len = self.plane_size() / mem::size_of::<T>();
+        // Planes allocated by `av_samples_alloc` are aligned.
+        Some(unsafe { slice::from_raw_parts(self.data[index].cast(), len) })
+    }
+
+    pub fn plane_mut<T: Sample>(&mut self, index: usize) -> Option<&mut [T]> {
+        if T::FORMAT != self.format.packed() {
+            return None;
+        }
+        let len = self.plane_size() / mem::size_of::<T>();
+        Some(unsafe { slice::from_raw_parts_mut(self.data[index].cast(), len) })
+    }
+
+    /// Fill with silence(`av_samples_set_silence`), which is not 0 for
+    /// `AV_SAMPLE_FMT_U8`.
+    pub fn set_silence(&mut self) -> Result<()> {
+        let (samples, channels) = (to_c_int(self.samples)?, to_c_int(self.channels)?);
+        unsafe {
+            ffi::av_samples_set_silence(
+                self.data.as_mut_ptr(),
+                0,
+                samples,
+                channels,
+                self.format.0,
+            );
+        }
+        Ok(())
+    }
+
+    /// Copy the samples of an audio frame, whose planes must be within its
+    /// buffers(`buf` and `extended_buf`).
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        let (format, channels, samples) = check_audio_frame(frame)?;
+        let buffer = Self::new(format, channels, samples)?;
+        check(unsafe {
+            ffi::av_samples_copy(
+                buffer.data.as_ptr() as _,
+                frame.extended_data as _,
+                0,
+                0,
+                frame.nb_samples,
+                frame.nb_channels(),
+                format.0,
+            )
+        })?;
+        Ok(buffer)
+    }
+
+    /// Allocate the buffers of an audio frame(`av_frame_get_buffer`) and copy
+    /// the samples into it. The format, the number of samples and the
+    /// default channel layout of `channels()` are set, the frame must not
+    /// have buffers.
+    pub fn to_frame(&self, frame: &mut Frame) -> Result<()> {
+        if !frame.buf[0].is_null() {
+            return Err(Error::INVALID_ARGUMENT);
+        }
+        let (samples, channels) = (to_c_int(self.samples)?, to_c_int(self.channels)?);
+        // The buffers are allocated from these fields.
+        let raw = unsafe { frame.as_mut_raw() };
+        raw.format = self.format.to_c_int();
+        raw.nb_samples = samples;
+        if raw.nb_channels() != channels {
+            raw.set_default_channel_layout(channels)?;
+        }
+        check(unsafe { ffi::av_frame_get_buffer(frame.as_mut_ptr(), 0) })?;
+        check(unsafe {
+            ffi::av_samples_copy(
+                frame.extended_data as _,
+                self.data.as_ptr() as _,
+                0,
+                0,
+                samples,
+                channels,
+                self.format.0,
+            )
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for SampleBuffer {
+    fn drop(&mut self) {
+        unsafe { ffi::av_freep(self.data.as_mut_ptr().cast()) };
+    }
+}
+
+impl fmt::Debug for SampleBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SampleBuffer")
+            .field("format", &self.format)
+            .field("channels", &self.channels)
+            .field("samples", &self.samples)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_format() {
+        let fltp: SampleFormat = "fltp".parse().unwrap();
+        assert_eq!(fltp, SampleFormat::from(ffi::AV_SAMPLE_FMT_FLTP));
+        assert!(fltp.is_planar());
+        assert_eq!(fltp.packed(), f32::FORMAT);
+        assert_eq!(fltp.packed().planar(), fltp);
+        assert_eq!(fltp.bytes_per_sample(), 4);
+        assert_eq!(fltp.buffer_size(2, 100), Ok(800));
+        assert_eq!(fltp.to_string(), "fltp");
+        assert!("no_such_format".parse::<SampleFormat>().is_err());
+    }
+
+    #[test]
+    fn test_sample_buffer() {
+        let mut buffer = SampleBuffer::new(ffi::AV_SAMPLE_FMT_S16P.into(), 2, 64).unwrap();
+        assert_eq!(buffer.planes(), 2);
+        assert!(buffer.plane::<f32>(0).is_none());
+        assert!(buffer.plane::<i16>(1).unwrap().iter().all(|x| *x == 0));
+        buffer.plane_mut::<i16>(1).unwrap()[3] = 42;
+
+        let u8_buffer = SampleBuffer::new(ffi::AV_SAMPLE_FMT_U8.into(), 2, 64).unwrap();
+        assert_eq!(u8_buffer.planes(), 1);
+        assert_eq!(u8_buffer.plane::<u8>(0).unwrap(), &[0x80; 128]);
+
+        let mut frame = Frame::new().unwrap();
+        buffer.to_frame(&mut frame).unwrap();
+        let copied = SampleBuffer::from_frame(&frame).unwrap();
+        assert_eq!(copied.plane::<i16>(1), buffer.plane::<i16>(1));
+
+        // Planes pointing out of the buffers.
+        unsafe { frame.as_mut_raw().nb_samples = 1 << 20 };
+        assert!(SampleBuffer::from_frame(&frame).is_err());
+        let empty = Frame::new().unwrap();
+        assert!(SampleBuffer::from_frame(&empty).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+//! Seek to an exact frame, not only to the keyframe before it which
+//! `avformat_seek_file` and `av_seek_frame` land on.
+use crate::error::{check, Error, Result};
+use crate::ffi;
+use crate::frame::Frame;
+use crate::packet::Packet;
+use std::ffi::c_int;
+use std::ptr;
+
+/// Where to seek to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekTarget {
+    /// A timestamp in the time base of the stream.
+    Timestamp(i64),
+    /// A frame number from the start of the stream, converted to a timestamp
+    /// by the average frame rate, which is exact for constant frame rate.
+    Frame(i64),
+    /// A byte position in the file(`AVSEEK_FLAG_BYTE`), yielding the first
+    /// frame decoded from there.
+    Byte(i64),
+}
+
+fn stream(fmt_ctx: &ffi::AVFormatContext, stream_index: usize) -> Result<&ffi::AVStream> {
+    if stream_index >= fmt_ctx.nb_streams as usize {
+        return Err(Error::INVALID_ARGUMENT);
+    }
+    Ok(unsafe { &**fmt_ctx.streams.add(stream_index) })
+}
+
+/// Timestamp of a frame number by the average frame rate, or the real base
+/// frame rate if the average is unknown.
+fn frame_number_to_timestamp(stream: &ffi::AVStream, frame_number: i64) -> Result<i64> {
+    let frame_rate = if stream.avg_frame_rate.num > 0 {
+        stream.avg_frame_rate
+    } else {
+        stream.r_frame_rate
+    };
+    if frame_rate.num <= 0 || frame_rate.den <= 0 {
+        return Err(Error::INVALID_ARGUMENT);
+    }
+    let start_time = if stream.start_time == ffi::AV_NOPTS_VALUE {
+        0
+    } else {
+        stream.start_time
+    };
+    let offset =
+        unsafe { ffi::av_rescale_q(frame_number, ffi::av_inv_q(frame_rate), stream.time_base) };
+    Ok(start_time + offset)
+}
+
+fn frame_timestamp(frame: &ffi::AVFrame) -> i64 {
+    if frame.best_effort_timestamp == ffi::AV_NOPTS_VALUE {
+        frame.pts
+    } else {
+        frame.best_effort_timestamp
+    }
+}
+
+/// Decode the next frame of the stream into `frame`, reading packets into
+/// `packet` as needed and draining the decoder at the end of the file,
+/// [`Error::EOF`] after the last frame.
+fn next_frame(
+    fmt_ctx: &mut ffi::AVFormatContext,
+    dec_ctx: &mut ffi::AVCodecContext,
+    stream_index: usize,
+    frame: &mut Frame,
+    packet: &mut Packet,
+) -> Result<()> {
+    loop {
+        match check(unsafe { ffi::avcodec_receive_frame(dec_ctx, frame.as_mut_ptr()) }) {
+            Ok(_) => return Ok(()),
+            Err(Error::AGAIN) => {}
+            Err(err) => return Err(err),
+        }
+        loop {
+            match check(unsafe { ffi::av_read_frame(fmt_ctx, packet.as_mut_ptr()) }) {
+                Ok(_) if packet.stream_index as usize != stream_index => packet.unref(),
+                Ok(_) => {
+                    let ret = unsafe { ffi::avcodec_send_packet(dec_ctx, packet.as_ptr()) };
+                    packet.unref();
+                    check(ret)?;
+                    break;
+                }
+                Err(Error::EOF) => {
+                    check(unsafe { ffi::avcodec_send_packet(dec_ctx, ptr::null()) })?;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Seek to the keyframe at or before `timestamp`, falling back to
+/// `av_seek_frame` with `AVSEEK_FLAG_BACKWARD` if `avformat_seek_file` fails.
+fn seek_backward(
+    fmt_ctx: &mut ffi::AVFormatContext,
+    stream_index: usize,
+    timestamp: i64,
+) -> Result<()> {
+    let stream_index = stream_index as c_int;
+    let ret = unsafe {
+        ffi::avformat_seek_file(fmt_ctx, stream_index, i64::MIN, timestamp, timestamp, 0)
+    };
+    if ret < 0 {
+        check(unsafe {
+            ffi::av_seek_frame(
+                fmt_ctx,
+                stream_index,
+                timestamp,
+                ffi::AVSEEK_FLAG_BACKWARD as c_int,
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn seek_timestamp(
+    fmt_ctx: &mut ffi::AVFormatContext,
+    dec_ctx: &mut ffi::AVCodecContext,
+    stream_index: usize,
+    timestamp: i64,
+    frame: &mut Frame,
+    packet: &mut Packet,
+) -> Result<()> {
+    let stream = stream(fmt_ctx, stream_index)?;
+    let start_time = if stream.start_time == ffi::AV_NOPTS_VALUE {
+        0
+    } else {
+        stream.start_time
+    };
+    let time_base = stream.time_base;
+    let one_second = (i64::from(time_base.den) / i64::from(time_base.num.max(1))).max(1);
+
+    // Without an index the demuxer may land after the target, then seek
+    // further back until the first decoded frame is not after it.
+    let mut back = 0i64;
+    loop {
+        let seek_timestamp = timestamp.saturating_sub(back);
+        seek_backward(fmt_ctx, stream_index, seek_timestamp)?;
+        unsafe { ffi::avcodec_flush_buffers(dec_ctx) };
+
+        next_frame(fmt_ctx, dec_ctx, stream_index, frame, packet)?;
+        let first_timestamp = frame_timestamp(frame);
+        if first_timestamp != ffi::AV_NOPTS_VALUE
+            && first_timestamp > timestamp
+            && seek_timestamp > start_time
+        {
+            back = back.saturating_mul(2).max(one_second);
+            continue;
+        }
+        // Frames come in presentation order, so B-frames are handled by the
+        // decoder.
+        loop {
+            let current = frame_timestamp(frame);
+            if current != ffi::AV_NOPTS_VALUE && current >= timestamp {
+                return Ok(());
+            }
+            next_frame(fmt_ctx, dec_ctx, stream_index, frame, packet)?;
+        }
+    }
+}
+
+/// Seek the stream `stream_index` and return the first frame whose
+/// `best_effort_timestamp` is at or after the target, with the frames
+/// before it decoded and discarded.
+///
+/// `dec_ctx` must be a decoder opened for the stream. It's flushed
+/// (`avcodec_flush_buffers`) after seeking, and decoding can go on from the
+/// returned frame. Fails with [`Error::EOF`] if there is no such frame.
+pub fn seek_exact(
+    fmt_ctx: &mut ffi::AVFormatContext,
+    dec_ctx: &mut ffi::AVCodecContext,
+    stream_index: usize,
+    target: SeekTarget,
+) -> Result<Frame> {
+    // Reused by all the discarded frames.
+    let mut frame = Frame::new()?;
+    let mut packet = Packet::new()?;
+    match target {
+        SeekTarget::Timestamp(timestamp) => seek_timestamp(
+            fmt_ctx,
+            dec_ctx,
+            stream_index,
+            timestamp,
+            &mut frame,
+            &mut packet,
+        )?,
+        SeekTarget::Frame(frame_number) => {
+            let timestamp =
+                frame_number_to_timestamp(stream(fmt_ctx, stream_index)?, frame_number)?;
+            seek_timestamp(
+                fmt_ctx,
+                dec_ctx,
+                stream_index,
+                timestamp,
+                &mut frame,
+                &mut packet,
+            )?
+        }
+        SeekTarget::Byte(pos) => {
+            stream(fmt_ctx, stream_index)?;
+            let flags = ffi::AVSEEK_FLAG_BYTE as c_int;
+            check(unsafe { ffi::av_seek_frame(fmt_ctx, -1, pos, flags) })?;
+            unsafe { ffi::avcodec_flush_buffers(dec_ctx) };
+            next_frame(fmt_ctx, dec_ctx, stream_index, &mut frame, &mut packet)?
+        }
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::{CStr, CString};
+    use std::path::PathBuf;
+    use std::{env, fs, mem, process, slice};
+
+    const FRAMES: i64 = 30;
+
+    /// Encode `FRAMES` frames of 64x64 MPEG-4 video at 25 fps with a keyframe
+    /// every 10 frames and up to 2 B-frames in a row into an MPEG-TS file, `false` if the encoder or the
+    /// muxer is unavailable.
+    fn write_test_file(path: &CStr) -> bool {
+        unsafe {
+            let codec = ffi::avcodec_find_encoder(ffi::AV_CODEC_ID_MPEG4);
+            let mut fmt_ctx = ptr::null_mut();
+            let ret = ffi::avformat_alloc_output_context2(
+                &mut fmt_ctx,
+                ptr::null_mut(),
+                c"mpegts".as_ptr(),
+                path.as_ptr(),
+            );
+            if codec.is_null() || ret < 0 {
+                ffi::avformat_free_context(fmt_ctx);
+                return false;
+            }
+            let time_base = ffi::AVRational { num: 1, den: 25 };
+            let mut enc_ctx = ffi::avcodec_alloc_context3(codec);
+            (*enc_ctx).width = 64;
+            (*enc_ctx).height = 64;
+            (*enc_ctx).pix_fmt = ffi::AV_PIX_FMT_YUV420P;
+            (*enc_ctx).time_base = time_base;
+            (*enc_ctx).framerate = ffi::av_inv_q(time_base);
+            (*enc_ctx).gop_size = 10;
+            (*enc_ctx).max_b_frames = 2;
+            check(ffi::avcodec_open2(enc_ctx, codec, ptr::null_mut())).unwrap();
+            let stream = ffi::avformat_new_stream(fmt_ctx, ptr::null());
+            check(ffi::avcodec_parameters_from_context(
+                (*stream).codecpar,
+                enc_ctx,
+            ))
+            .unwrap();
+            (*stream).time_base = time_base;
+            let flags = ffi::AVIO_FLAG_WRITE as c_int;
+            check(ffi::avio_open(&mut (*fmt_ctx).pb, path.as_ptr(), flags)).unwrap();
+            check(ffi::avformat_write_header(fmt_ctx, ptr::null_mut())).unwrap();
+            // Set by the muxer, 1/90000 for MPEG-TS.
+            let stream_time_base = (*stream).time_base;
+
+            let mut packet = Packet::new().unwrap();
+            let write_packets = |packet: &mut Packet| loop {
+                match check(ffi::avcodec_receive_packet(enc_ctx, packet.as_mut_ptr())) {
+                    Ok(_) => {
+                        ffi::av_packet_rescale_ts(packet.as_mut_ptr(), time_base, stream_time_base);
+                        packet.set_stream_index(0);
+                        check(ffi::av_interleaved_write_frame(
+                            fmt_ctx,
+                            packet.as_mut_ptr(),
+                        ))
+                        .unwrap();
+                    }
+                    Err(Error::AGAIN | Error::EOF) => break,
+                    Err(err) => panic!("{err:?}"),
+                }
+            };
+            for pts in 0..FRAMES {
+                let mut frame = Frame::new().unwrap();
+                let raw = frame.as_mut_raw();
+                raw.width = 64;
+                raw.height = 64;
+                #[cfg(feature = "typed_enums")]
+                let format = ffi::AV_PIX_FMT_YUV420P.0;
+                #[cfg(not(feature = "typed_enums"))]
+                let format = ffi::AV_PIX_FMT_YUV420P;
+                raw.format = format;
+                frame.set_pts(pts);
+                check(ffi::av_frame_get_buffer(frame.as_mut_ptr(), 0)).unwrap();
+                for (plane, height) in [64, 32, 32].into_iter().enumerate() {
+                    let len = frame.linesize[plane] as usize * height;
+                    slice::from_raw_parts_mut(frame.data[plane], len).fill(pts as u8 * 4);
+                }
+                check(ffi::avcodec_send_frame(enc_ctx, frame.as_ptr())).unwrap();
+                write_packets(&mut packet);
+            }
+            check(ffi::avcodec_send_frame(enc_ctx, ptr::null())).unwrap();
+            write_packets(&mut packet);
+            check(ffi::av_write_trailer(fmt_ctx)).unwrap();
+            ffi::avio_closep(&mut (*fmt_ctx).pb);
+            ffi::avformat_free_context(fmt_ctx);
+            ffi::avcodec_free_context(&mut enc_ctx);
+        }
+        true
+    }
+
+    /// An opened input and a decoder of its first stream.
+    struct Input {
+        fmt_ctx: *mut ffi::AVFormatContext,
+        dec_ctx: *mut ffi::AVCodecContext,
+    }
+
+    impl Input {
+        fn open(path: &CStr) -> Self {
+            unsafe {
+                let mut fmt_ctx = ptr::null_mut();
+                check(ffi::avformat_open_input(
+                    &mut fmt_ctx,
+                    path.as_ptr(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                ))
+                .unwrap();
+                check(ffi::avformat_find_stream_info(fmt_ctx, ptr::null_mut())).unwrap();
+                let par = stream(&*fmt_ctx, 0).unwrap().codecpar;
+                let decoder = ffi::avcodec_find_decoder((*par).codec_id);
+                let dec_ctx = ffi::avcodec_alloc_context3(decoder);
+                check(ffi::avcodec_parameters_to_context(dec_ctx, par)).unwrap();
+                check(ffi::avcodec_open2(dec_ctx, decoder, ptr::null_mut())).unwrap();
+                Self { fmt_ctx, dec_ctx }
+            }
+        }
+
+        /// pts, byte position and keyframe flag of the packets, in decoding
+        /// order.
+        fn packets(&mut self) -> Vec<(i64, i64, bool)> {
+            let mut packets = Vec::new();
+            let mut packet = Packet::new().unwrap();
+            while unsafe { ffi::av_read_frame(self.fmt_ctx, packet.as_mut_ptr()) } >= 0 {
+                let key = packet.flags & ffi::AV_PKT_FLAG_KEY as c_int != 0;
+                packets.push((packet.pts, packet.pos, key));
+                packet.unref();
+            }
+            packets
+        }
+
+        fn seek(&mut self, target: SeekTarget) -> Result<i64> {
+            let frame = unsafe { seek_exact(&mut *self.fmt_ctx, &mut *self.dec_ctx, 0, target) }?;
+            Ok(frame.best_effort_timestamp)
+        }
+    }
+
+    impl Drop for Input {
+        fn drop(&mut self) {
+            unsafe {
+                ffi::avcodec_free_context(&mut self.dec_ctx);
+                ffi::avformat_close_input(&mut self.fmt_ctx);
+            }
+        }
+    }
+
+    /// Removes the file when dropped, also if the test fails.
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_seek_exact() {
+        let file =
+            TempFile(env::temp_dir().join(format!("rusty_ffmpeg_seek_{}.ts", process::id())));
+        let path = CString::new(file.0.to_str().unwrap()).unwrap();
+        if !write_test_file(&path) {
+            return;
+        }
+        let mut packets = Input::open(&path).packets();
+        assert_eq!(packets.len(), FRAMES as usize);
+        // Reordered by the B-frames.
+        assert!(packets.windows(2).any(|x| x[0].0 > x[1].0));
+        packets.sort_unstable();
+        // Frame `i` is at `start + i * 3600` in 1/90000.
+        let start = packets[0].0;
+        let frame_pts = |i: usize| start + i as i64 * 3600;
+        assert!((0..packets.len()).all(|i| packets[i].0 == frame_pts(i)));
+        let keyframes: Vec<_> = (0..packets.len()).filter(|&i| packets[i].2).collect();
+        assert!(keyframes.len() >= 2);
+        // A B-frame, decoded after the frame following it.
+        let target = keyframes[1] + 1;
+
+        let mut input = Input::open(&path);
+        assert_eq!(
+            input.seek(SeekTarget::Timestamp(frame_pts(target))),
+            Ok(frame_pts(target))
+        );
+        // Between two frames, the later one.
+        assert_eq!(
+            input.seek(SeekTarget::Timestamp(frame_pts(target) + 1)),
+            Ok(frame_pts(target + 1))
+        );
+        // Backward after forward, to the frame before the keyframe, which is
+        // decoded starting from the previous keyframe.
+        let target = keyframes[1] - 1;
+        assert_eq!(
+            input.seek(SeekTarget::Frame(target as i64)),
+            Ok(frame_pts(target))
+        );
+        let after_last = frame_pts(packets.len() - 1) + 1;
+        assert_eq!(
+            input.seek(SeekTarget::Timestamp(after_last)),
+            Err(Error::EOF)
+        );
+
+        // The first frame decoded from a keyframe packet is the keyframe.
+        let (pts, pos, _) = packets[keyframes[1]];
+        assert_eq!(input.seek(SeekTarget::Byte(pos)), Ok(pts));
+    }
+
+    #[test]
+    fn test_frame_number_to_timestamp() {
+        let mut stream: ffi::AVStream = unsafe { mem::zeroed() };
+        stream.time_base = ffi::AVRational { num: 1, den: 90000 };
+        stream.start_time = ffi::AV_NOPTS_VALUE;
+        assert!(frame_number_to_timestamp(&stream, 10).is_err());
+        stream.avg_frame_rate = ffi::AVRational {
+            num: 30000,
+            den: 1001,
+        };
+        assert_eq!(frame_number_to_timestamp(&stream, 10), Ok(30030));
+        stream.start_time = 3000;
+        assert_eq!(frame_number_to_timestamp(&stream, 0), Ok(3000));
+    }
+}
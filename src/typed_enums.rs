@@ -0,0 +1,74 @@
+//! `TryFrom<c_int>` and `Display` for the FFmpeg enums generated as newtypes
+//! with the `typed_enums` feature.
+use crate::ffi::*;
+use std::ffi::{c_char, c_int, CStr};
+use std::fmt;
+
+/// The integer is not a value of the FFmpeg enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidEnumValue(pub c_int);
+
+impl fmt::Display for InvalidEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid FFmpeg enum value: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidEnumValue {}
+
+fn write_name(f: &mut fmt::Formatter<'_>, name: *const c_char) -> fmt::Result {
+    if name.is_null() {
+        f.write_str("unknown")
+    } else {
+        f.write_str(&unsafe { CStr::from_ptr(name) }.to_string_lossy())
+    }
+}
+
+macro_rules! typed_enum {
+    ($ty:ident, $none:ident, |$value:ident| $is_valid:expr, $name:ident) => {
+        impl TryFrom<c_int> for $ty {
+            type Error = InvalidEnumValue;
+
+            fn try_from(value: c_int) -> Result<Self, Self::Error> {
+                let $value = $ty(value.try_into().map_err(|_| InvalidEnumValue(value))?);
+                if $value == $none || $is_valid {
+                    Ok($value)
+                } else {
+                    Err(InvalidEnumValue(value))
+                }
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write_name(f, unsafe { $name(*self) })
+            }
+        }
+    };
+}
+
+// Values are valid if the linked FFmpeg knows them.
+typed_enum!(
+    AVCodecID,
+    AV_CODEC_ID_NONE,
+    |id| !unsafe { avcodec_descriptor_get(id) }.is_null(),
+    avcodec_get_name
+);
+typed_enum!(
+    AVMediaType,
+    AVMEDIA_TYPE_UNKNOWN,
+    |media_type| !unsafe { av_get_media_type_string(media_type) }.is_null(),
+    av_get_media_type_string
+);
+typed_enum!(
+    AVPixelFormat,
+    AV_PIX_FMT_NONE,
+    |pix_fmt| !unsafe { av_pix_fmt_desc_get(pix_fmt) }.is_null(),
+    av_get_pix_fmt_name
+);
+typed_enum!(
+    AVSampleFormat,
+    AV_SAMPLE_FMT_NONE,
+    |sample_fmt| !unsafe { av_get_sample_fmt_name(sample_fmt) }.is_null(),
+    av_get_sample_fmt_name
+);